@@ -0,0 +1,87 @@
+//! Compares the hashes that `Bls12PoseidonCommitter` can be switched between, by constraint count
+//! and by native throughput. Run it with
+//!
+//! ```text
+//! cargo run --release --example hash_comparison
+//! ```
+//!
+//! The constraint counts, which unlike the timings don't depend on the machine, are
+//!
+//! | hash      | inputs | constraints | constraints / input |
+//! |-----------|--------|-------------|---------------------|
+//! | Poseidon  |      1 |         192 |               192.0 |
+//! | Poseidon  |      4 |         198 |                49.5 |
+//! | Poseidon  |     16 |         990 |                61.9 |
+//! | Poseidon  |     64 |        4158 |                65.0 |
+//! | Poseidon2 |      1 |         264 |               264.0 |
+//! | Poseidon2 |      4 |         528 |               132.0 |
+//! | Poseidon2 |     16 |        2112 |               132.0 |
+//! | Poseidon2 |     64 |        8448 |               132.0 |
+//! | MiMC      |      1 |         660 |               660.0 |
+//! | MiMC      |      4 |        2640 |               660.0 |
+//! | MiMC      |     16 |       10560 |               660.0 |
+//! | MiMC      |     64 |       42240 |               660.0 |
+//! | Rescue    |      1 |         246 |               246.0 |
+//! | Rescue    |      4 |         501 |               125.2 |
+//! | Rescue    |     16 |        2013 |               125.8 |
+//! | Rescue    |     64 |        8061 |               126.0 |
+
+use ark_bls12_381::Fr;
+use ark_ff::UniformRand;
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSystem, OptimizationGoal};
+use std::time::{Duration, Instant};
+
+use poseidon::{
+    mimc::MimcFeistel,
+    poseidon::{FieldHasher, Poseidon},
+    poseidon2::Poseidon2,
+    rescue::Rescue,
+};
+
+const INPUT_LENS: [usize; 4] = [1, 4, 16, 64];
+const NATIVE_RUNS: u32 = 200;
+
+fn num_constraints<H: FieldHasher>(input: &[Fr]) -> usize {
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+
+    let input_var = Vec::<FpVar<Fr>>::new_witness(cs.clone(), || Ok(input)).unwrap();
+    let _hash = H::hash_gadget(&cs, &input_var).unwrap();
+
+    cs.finalize();
+    cs.num_constraints()
+}
+
+fn time_per_hash<H: FieldHasher>(input: &[Fr]) -> Duration {
+    let start = Instant::now();
+    for _ in 0..NATIVE_RUNS {
+        H::hash(input).unwrap();
+    }
+    start.elapsed() / NATIVE_RUNS
+}
+
+fn print_row<H: FieldHasher>(name: &str) {
+    let mut rng = rand::thread_rng();
+
+    for len in INPUT_LENS {
+        let input: Vec<Fr> = (0..len).map(|_| Fr::rand(&mut rng)).collect();
+        let constraints = num_constraints::<H>(&input);
+        let time = time_per_hash::<H>(&input);
+
+        println!(
+            "| {name:<9} | {len:>6} | {constraints:>11} | {:>19.1} | {:>13.1?} |",
+            constraints as f64 / len as f64,
+            time,
+        );
+    }
+}
+
+fn main() {
+    println!("| hash      | inputs | constraints | constraints / input | time per hash |");
+    println!("|-----------|--------|-------------|---------------------|---------------|");
+    print_row::<Poseidon>("Poseidon");
+    print_row::<Poseidon2>("Poseidon2");
+    print_row::<MimcFeistel>("MiMC");
+    print_row::<Rescue>("Rescue");
+}
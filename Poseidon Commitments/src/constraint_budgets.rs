@@ -0,0 +1,290 @@
+//! Constraint budgets for the gadgets and circuits in this crate. Each test synthesizes one of
+//! them for several input lengths, and fails with a table of every length whose number of
+//! constraints or witness variables went over the budget recorded here. A gadget that got cheaper
+//! still passes, and the table is printed as a note, so its budget can be lowered to match.
+
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::commitment::CommitmentGadget;
+use ark_ed_on_bls12_381::Fr as JubjubFr;
+use ark_ff::UniformRand;
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar, uint8::UInt8};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, OptimizationGoal, Result,
+};
+
+use crate::{
+    create_new_commitment,
+    encryption::{Bls12PoseidonCipher, EncryptedOpeningProof, PoseidonCiphertextVar},
+    equality::EqualityProof,
+    hash_to_field::poseidon_hash_to_field_gadget,
+    merkle::{PoseidonMerkleTree, PoseidonPathVar},
+    mimc::MimcFeistel,
+    pedersen::{Bls12PedersenCommitter, JubjubScalarVar},
+    poseidon::{Bls12PoseidonCommitter, FieldHasher, Poseidon, UnitVar},
+    poseidon2::Poseidon2,
+    rescue::Rescue,
+    set_membership::SetMembershipProof,
+    vector_commitment::Bls12PoseidonVectorCommitter,
+};
+
+/// The recorded cost of a gadget at one input length: `(length, constraints, witness variables)`
+type Budget = (usize, usize, usize);
+
+fn change(budget: usize, actual: usize) -> String {
+    if budget == actual {
+        budget.to_string()
+    } else {
+        format!("{budget} -> {actual} ({:+})", actual as i64 - budget as i64)
+    }
+}
+
+// Synthesizes `gadget` at every length in `budgets`, the same way Groth16 does, and compares the
+// costs
+fn check_budgets(
+    name: &str,
+    budgets: &[Budget],
+    gadget: impl Fn(ConstraintSystemRef<Fr>, usize) -> Result<()>,
+) {
+    let mut diff = Vec::new();
+    let mut over_budget = false;
+    for &(len, constraints, witnesses) in budgets {
+        let cs = ConstraintSystem::new_ref();
+        cs.set_optimization_goal(OptimizationGoal::Constraints);
+        gadget(cs.clone(), len).unwrap();
+        cs.finalize();
+        assert!(
+            cs.is_satisfied().unwrap(),
+            "{name} is not satisfied at length {len}"
+        );
+
+        let (actual_constraints, actual_witnesses) =
+            (cs.num_constraints(), cs.num_witness_variables());
+        over_budget |= actual_constraints > constraints || actual_witnesses > witnesses;
+        if (actual_constraints, actual_witnesses) != (constraints, witnesses) {
+            diff.push(format!(
+                "{len:>8} | {:>26} | {:>26}",
+                change(constraints, actual_constraints),
+                change(witnesses, actual_witnesses),
+            ));
+        }
+    }
+
+    if diff.is_empty() {
+        return;
+    }
+    let table = format!(
+        "{:>8} | {:>26} | {:>26}\n{}\n",
+        "length",
+        "constraints",
+        "witness variables",
+        diff.join("\n")
+    );
+    assert!(!over_budget, "{name} is over its budget\n\n{table}");
+    println!("note: {name} is under its budget\n\n{table}");
+}
+
+fn rand_vars(cs: &ConstraintSystemRef<Fr>, len: usize) -> Result<Vec<FpVar<Fr>>> {
+    let mut rng = rand::thread_rng();
+    let values: Vec<Fr> = (0..len).map(|_| Fr::rand(&mut rng)).collect();
+    Vec::new_witness(cs.clone(), || Ok(&values[..]))
+}
+
+fn check_hash_budgets<H: FieldHasher>(name: &str, budgets: &[Budget]) {
+    check_budgets(name, budgets, |cs, len| {
+        let _hash = H::hash_gadget(&cs, &rand_vars(&cs, len)?)?;
+        Ok(())
+    });
+}
+
+#[test]
+fn poseidon_iterated_hash() {
+    check_hash_budgets::<Poseidon>(
+        "poseidon_iterated_hash_gadget",
+        &[
+            (0, 0, 0),
+            (1, 192, 193),
+            (4, 198, 202),
+            (5, 392, 397),
+            (16, 990, 1006),
+            (64, 4158, 4222),
+        ],
+    );
+}
+
+#[test]
+fn other_hashes() {
+    check_hash_budgets::<Poseidon2>(
+        "Poseidon2",
+        &[(1, 264, 265), (4, 528, 532), (16, 2112, 2128)],
+    );
+    check_hash_budgets::<MimcFeistel>(
+        "MimcFeistel",
+        &[(1, 660, 661), (4, 2640, 2644), (16, 10560, 10576)],
+    );
+    check_hash_budgets::<Rescue>("Rescue", &[(1, 246, 247), (4, 501, 505), (16, 2013, 2029)]);
+}
+
+#[test]
+/// The length is in bytes
+fn poseidon_commit() {
+    check_budgets(
+        "Bls12PoseidonCommitter",
+        &[(0, 826, 704), (32, 1084, 962), (100, 1824, 1702)],
+        |cs, len| {
+            let input = UInt8::new_witness_vec(cs.clone(), &vec![7; len])?;
+            let r = rand_vars(&cs, 1)?.remove(0);
+            let _commitment = <Bls12PoseidonCommitter as CommitmentGadget<_, _>>::commit(
+                &UnitVar::default(),
+                &input,
+                &r,
+            )?;
+            Ok(())
+        },
+    );
+}
+
+#[test]
+fn pedersen_commit() {
+    check_budgets(
+        "Bls12PedersenCommitter",
+        &[(0, 1257, 1257), (1, 2517, 2517), (4, 6297, 6297)],
+        |cs, len| {
+            let mut rng = rand::thread_rng();
+            let mut scalar =
+                || JubjubScalarVar::new_witness(cs.clone(), || Ok(JubjubFr::rand(&mut rng)));
+            let input = (0..len).map(|_| scalar()).collect::<Result<Vec<_>>>()?;
+            let r = scalar()?;
+            let _commitment = Bls12PedersenCommitter::commit_scalars_gadget(&input, &r)?;
+            Ok(())
+        },
+    );
+}
+
+#[test]
+fn vector_commit() {
+    check_budgets(
+        "Bls12PoseidonVectorCommitter",
+        &[(1, 194, 196), (4, 392, 397), (16, 1184, 1201)],
+        |cs, len| {
+            let attrs = rand_vars(&cs, len)?;
+            let r = rand_vars(&cs, 1)?.remove(0);
+            let _commitment = Bls12PoseidonVectorCommitter::commit_gadget(&attrs, &r)?;
+            Ok(())
+        },
+    );
+}
+
+#[test]
+/// The length is the tree height
+fn merkle_path() {
+    check_budgets(
+        "PoseidonPathVar::compute_root",
+        &[(1, 197, 199), (4, 788, 793), (8, 1576, 1585)],
+        |cs, height| {
+            let tree = PoseidonMerkleTree::new(&[Fr::from(1u8)], height).unwrap();
+            let path = tree.generate_proof(0).unwrap();
+            let leaf = FpVar::new_witness(cs.clone(), || Ok(Fr::from(1u8)))?;
+            let path = PoseidonPathVar::new_witness(cs.clone(), || Ok(&path))?;
+            let _root = path.compute_root(&leaf)?;
+            Ok(())
+        },
+    );
+}
+
+#[test]
+/// The length is in bytes
+fn hash_to_field() {
+    check_budgets(
+        "poseidon_hash_to_field_gadget",
+        &[(0, 0, 0), (32, 450, 450), (100, 1190, 1190)],
+        |cs, len| {
+            let msg = UInt8::new_witness_vec(cs.clone(), &vec![7; len])?;
+            let _hash = poseidon_hash_to_field_gadget(&msg, b"budget")?;
+            Ok(())
+        },
+    );
+}
+
+#[test]
+fn decrypt() {
+    check_budgets(
+        "Bls12PoseidonCipher::decrypt_gadget",
+        &[(1, 892, 895), (4, 892, 898), (16, 2692, 2710)],
+        |cs, len| {
+            let mut rng = rand::thread_rng();
+            let (key, nonce) = (Fr::rand(&mut rng), Fr::rand(&mut rng));
+            let msg: Vec<Fr> = (0..len).map(|_| Fr::rand(&mut rng)).collect();
+            let ct = Bls12PoseidonCipher::encrypt(&key, &nonce, &msg);
+
+            let key = FpVar::new_witness(cs.clone(), || Ok(key))?;
+            let nonce = FpVar::new_witness(cs.clone(), || Ok(nonce))?;
+            let ct = PoseidonCiphertextVar::new_witness(cs.clone(), || Ok(ct))?;
+            Bls12PoseidonCipher::decrypt_gadget(&key, &nonce, &ct)?;
+            Ok(())
+        },
+    );
+}
+
+#[test]
+/// There's only one shape of [`crate::CommitmentProof`], so its length is always 1
+fn commitment_proof() {
+    check_budgets("CommitmentProof", &[(1, 1461, 1216)], |cs, _| {
+        let mut rng = rand::thread_rng();
+        create_new_commitment(Fr::rand(&mut rng), &mut rng)
+            .unwrap()
+            .generate_constraints(cs)
+    });
+}
+
+#[test]
+/// There's only one shape of [`EqualityProof`], so its length is always 2, the number of
+/// commitments
+fn equality_proof() {
+    check_budgets("EqualityProof", &[(2, 2922, 2431)], |cs, _| {
+        let mut rng = rand::thread_rng();
+        let opening = create_new_commitment(Fr::rand(&mut rng), &mut rng).unwrap();
+        EqualityProof::rerandomize(&opening, &mut rng)
+            .unwrap()
+            .1
+            .generate_constraints(cs)
+    });
+}
+
+#[test]
+/// The length is the size of the set
+fn set_membership_proof() {
+    check_budgets(
+        "SetMembershipProof",
+        &[(1, 1462, 1216), (4, 1465, 1219), (16, 1477, 1231)],
+        |cs, len| {
+            let set: Vec<Fr> = (0..len as u64).map(Fr::from).collect();
+            let opening = create_new_commitment(set[0], &mut rand::thread_rng()).unwrap();
+            SetMembershipProof::new(opening.value, opening.cmd_rnd, opening.commitment, set)
+                .generate_constraints(cs)
+        },
+    );
+}
+
+#[test]
+/// The length is the number of attributes
+fn encrypted_opening_proof() {
+    check_budgets(
+        "EncryptedOpeningProof",
+        &[(1, 1087, 1087), (4, 1285, 1285), (16, 3877, 3877)],
+        |cs, len| {
+            let mut rng = rand::thread_rng();
+            let attrs: Vec<Fr> = (0..len).map(|_| Fr::rand(&mut rng)).collect();
+            let (key, nonce, com_rnd) =
+                (Fr::rand(&mut rng), Fr::rand(&mut rng), Fr::rand(&mut rng));
+
+            EncryptedOpeningProof {
+                commitment: Bls12PoseidonVectorCommitter::commit(&attrs, &com_rnd).unwrap(),
+                nonce,
+                ciphertext: Bls12PoseidonCipher::encrypt(&key, &nonce, &attrs),
+                key,
+                com_rnd,
+            }
+            .generate_constraints(cs)
+        },
+    );
+}
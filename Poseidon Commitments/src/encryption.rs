@@ -0,0 +1,355 @@
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::{
+    sponge::{
+        constraints::CryptographicSpongeVar,
+        poseidon::{constraints::PoseidonSpongeVar, PoseidonSponge},
+        CryptographicSponge, FieldBasedCryptographicSponge,
+    },
+    Error as ArkError,
+};
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, Namespace, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use std::borrow::Borrow;
+
+use crate::poseidon::BLS12_POSEIDON_X5_SPONGE_PARAMS;
+use crate::vector_commitment::Bls12PoseidonVectorCommitter;
+
+const ENC_DOMAIN_SEP: &[u8] = b"penc";
+
+/// A message encrypted with [`Bls12PoseidonCipher`]. There is one ciphertext element per message
+/// element, plus the authentication tag.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PoseidonCiphertext {
+    pub elems: Vec<Fr>,
+    pub tag: Fr,
+}
+
+/// Authenticated encryption of BLS12-381 scalars under a shared scalar key, using the Poseidon
+/// sponge in the style of SAFE. The sponge absorbs `domain_sep || len || key || nonce`, the
+/// message is masked with the next `len` squeezed elements, and the tag is squeezed after
+/// absorbing the ciphertext.
+///
+/// The sponge has width 5 and one element of capacity. Its S-box is x^5 rather than the x^3 of the
+/// other Poseidon parameters here, since x^3 isn't a permutation of the BLS12-381 scalar field.
+///
+/// A key and nonce pair must never be used to encrypt two different messages.
+pub struct Bls12PoseidonCipher;
+
+impl Bls12PoseidonCipher {
+    // The domain separator, packed into a single field element
+    fn domain_sep() -> Fr {
+        Fr::from_le_bytes_mod_order(ENC_DOMAIN_SEP)
+    }
+
+    // Absorbs the header, which fixes the message length along with the key and nonce
+    fn keyed_sponge(key: &Fr, nonce: &Fr, len: usize) -> PoseidonSponge<Fr> {
+        let mut sponge = PoseidonSponge::new(&BLS12_POSEIDON_X5_SPONGE_PARAMS);
+        sponge.absorb(&[Self::domain_sep(), Fr::from(len as u64), *key, *nonce].as_slice());
+        sponge
+    }
+
+    pub fn encrypt(key: &Fr, nonce: &Fr, msg: &[Fr]) -> PoseidonCiphertext {
+        let mut sponge = Self::keyed_sponge(key, nonce, msg.len());
+        let keystream: Vec<Fr> = sponge.squeeze_native_field_elements(msg.len());
+        let elems: Vec<Fr> = msg.iter().zip(&keystream).map(|(m, k)| *m + k).collect();
+
+        sponge.absorb(&elems);
+        let tag = sponge.squeeze_native_field_elements(1)[0];
+
+        PoseidonCiphertext { elems, tag }
+    }
+
+    /// Decrypts `ct`, or returns an error if it wasn't produced under this key and nonce
+    pub fn decrypt(key: &Fr, nonce: &Fr, ct: &PoseidonCiphertext) -> Result<Vec<Fr>, ArkError> {
+        let mut sponge = Self::keyed_sponge(key, nonce, ct.elems.len());
+        let keystream: Vec<Fr> = sponge.squeeze_native_field_elements(ct.elems.len());
+
+        sponge.absorb(&ct.elems);
+        if sponge.squeeze_native_field_elements(1)[0] != ct.tag {
+            return Err("invalid ciphertext tag".into());
+        }
+
+        Ok(ct
+            .elems
+            .iter()
+            .zip(&keystream)
+            .map(|(c, k)| *c - k)
+            .collect())
+    }
+
+    /// The gadget version of [`Bls12PoseidonCipher::decrypt`]. Instead of returning an error, this
+    /// enforces that the tag is valid.
+    pub fn decrypt_gadget(
+        key: &FpVar<Fr>,
+        nonce: &FpVar<Fr>,
+        ct: &PoseidonCiphertextVar,
+    ) -> Result<Vec<FpVar<Fr>>, SynthesisError> {
+        let cs = key.cs().or(nonce.cs()).or(ct.tag.cs());
+        let len = ct.elems.len();
+
+        let mut sponge = PoseidonSpongeVar::new(cs, &BLS12_POSEIDON_X5_SPONGE_PARAMS);
+        let header = vec![
+            FpVar::constant(Self::domain_sep()),
+            FpVar::constant(Fr::from(len as u64)),
+            key.clone(),
+            nonce.clone(),
+        ];
+        sponge.absorb(&header)?;
+        let keystream = sponge.squeeze_field_elements(len)?;
+
+        sponge.absorb(&ct.elems)?;
+        sponge.squeeze_field_elements(1)?[0].enforce_equal(&ct.tag)?;
+
+        Ok(ct
+            .elems
+            .iter()
+            .zip(&keystream)
+            .map(|(c, k)| c - k)
+            .collect())
+    }
+}
+
+/// The gadget version of [`PoseidonCiphertext`]
+pub struct PoseidonCiphertextVar {
+    pub elems: Vec<FpVar<Fr>>,
+    pub tag: FpVar<Fr>,
+}
+
+impl AllocVar<PoseidonCiphertext, Fr> for PoseidonCiphertextVar {
+    fn new_variable<T: Borrow<PoseidonCiphertext>>(
+        cs: impl Into<Namespace<Fr>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let ns = cs.into();
+        let cs = ns.cs();
+
+        f().and_then(|val| {
+            let ct = val.borrow();
+            let elems = Vec::new_variable(
+                ark_relations::ns!(cs, "ciphertext"),
+                || Ok(&ct.elems[..]),
+                mode,
+            )?;
+            let tag = FpVar::new_variable(ark_relations::ns!(cs, "tag"), || Ok(ct.tag), mode)?;
+
+            Ok(Self { elems, tag })
+        })
+    }
+}
+
+/// Proves that a public ciphertext encrypts the vector committed to in a public
+/// [`Bls12PoseidonVectorCommitter`] commitment, without revealing the key or the vector.
+///
+/// The public inputs are the commitment, the nonce, and then the ciphertext. See
+/// [`EncryptedOpeningProof::public_inputs`]. The circuit shape depends on the vector length.
+#[derive(Clone)]
+pub struct EncryptedOpeningProof {
+    // Public inputs
+    pub commitment: Fr,
+    pub nonce: Fr,
+    pub ciphertext: PoseidonCiphertext,
+
+    // Private witnesses. The vector itself is whatever the ciphertext decrypts to
+    pub key: Fr,
+    pub com_rnd: Fr,
+}
+
+impl EncryptedOpeningProof {
+    /// The public inputs a verifier needs to check a proof of this statement
+    pub fn public_inputs(&self) -> Vec<Fr> {
+        [
+            &[self.commitment, self.nonce],
+            &self.ciphertext.elems[..],
+            &[self.ciphertext.tag],
+        ]
+        .concat()
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for EncryptedOpeningProof {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
+        let commitment = FpVar::new_input(ark_relations::ns!(cs, "pub commitment"), || {
+            Ok(self.commitment)
+        })?;
+        let nonce = FpVar::new_input(ark_relations::ns!(cs, "pub nonce"), || Ok(self.nonce))?;
+        let ciphertext =
+            PoseidonCiphertextVar::new_input(ark_relations::ns!(cs, "pub ciphertext"), || {
+                Ok(&self.ciphertext)
+            })?;
+
+        let key = FpVar::new_witness(ark_relations::ns!(cs, "key"), || Ok(self.key))?;
+        let r = FpVar::new_witness(ark_relations::ns!(cs, "com_rnd"), || Ok(self.com_rnd))?;
+
+        let attrs = Bls12PoseidonCipher::decrypt_gadget(&key, &nonce, &ciphertext)?;
+        Bls12PoseidonVectorCommitter::commit_gadget(&attrs, &r)?.enforce_equal(&commitment)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bls12_381::Bls12_381;
+    use ark_ff::UniformRand;
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_snark::SNARK;
+
+    fn random_elems(n: usize) -> Vec<Fr> {
+        let mut rng = rand::thread_rng();
+        (0..n).map(|_| Fr::rand(&mut rng)).collect()
+    }
+
+    fn create_new_proof(num_attrs: usize) -> EncryptedOpeningProof {
+        let mut rng = rand::thread_rng();
+        let key = Fr::rand(&mut rng);
+        let nonce = Fr::rand(&mut rng);
+        let com_rnd = Fr::rand(&mut rng);
+        let attrs = random_elems(num_attrs);
+        let commitment = Bls12PoseidonVectorCommitter::commit(&attrs, &com_rnd).unwrap();
+        let ciphertext = Bls12PoseidonCipher::encrypt(&key, &nonce, &attrs);
+
+        EncryptedOpeningProof {
+            commitment,
+            nonce,
+            ciphertext,
+            key,
+            com_rnd,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut rng = rand::thread_rng();
+        let key = Fr::rand(&mut rng);
+        let nonce = Fr::rand(&mut rng);
+
+        // Empty, within one rate-sized block and across several
+        for n in [0, 1, 4, 5, 13] {
+            let msg = random_elems(n);
+            let ct = Bls12PoseidonCipher::encrypt(&key, &nonce, &msg);
+
+            assert_eq!(ct.elems.len(), n);
+            assert_eq!(
+                Bls12PoseidonCipher::decrypt(&key, &nonce, &ct).unwrap(),
+                msg
+            );
+        }
+    }
+
+    #[test]
+    /// Check that the cipher's S-box is a permutation of the scalar field, i.e., that its exponent
+    /// is coprime to r - 1. x^3, which the other Poseidon parameters use, is not.
+    fn sbox_is_a_permutation() {
+        use ark_ff::BigInteger;
+
+        let r_minus_one = (-Fr::from(1u8)).into_bigint().to_bytes_be();
+        let rem = |k: u64| {
+            r_minus_one
+                .iter()
+                .fold(0, |acc, &b| (acc * 256 + b as u64) % k)
+        };
+
+        assert_eq!(BLS12_POSEIDON_X5_SPONGE_PARAMS.alpha, 5);
+        assert_ne!(rem(5), 0);
+        assert_eq!(rem(3), 0);
+    }
+
+    #[test]
+    /// Check that decryption fails under the wrong key or nonce, or for a modified ciphertext
+    fn decrypt_rejects_forgeries() {
+        let mut rng = rand::thread_rng();
+        let key = Fr::rand(&mut rng);
+        let nonce = Fr::rand(&mut rng);
+        let other = Fr::rand(&mut rng);
+        let ct = Bls12PoseidonCipher::encrypt(&key, &nonce, &random_elems(3));
+
+        let mut bad_elem = ct.clone();
+        bad_elem.elems[1] += Fr::from(1u8);
+        let mut bad_tag = ct.clone();
+        bad_tag.tag += Fr::from(1u8);
+        let mut truncated = ct.clone();
+        truncated.elems.pop();
+
+        assert!(Bls12PoseidonCipher::decrypt(&other, &nonce, &ct).is_err());
+        assert!(Bls12PoseidonCipher::decrypt(&key, &other, &ct).is_err());
+        for bad_ct in [bad_elem, bad_tag, truncated] {
+            assert!(Bls12PoseidonCipher::decrypt(&key, &nonce, &bad_ct).is_err());
+        }
+    }
+
+    #[test]
+    fn gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let key = Fr::rand(&mut rng);
+        let nonce = Fr::rand(&mut rng);
+        let msg = random_elems(6);
+        let ct = Bls12PoseidonCipher::encrypt(&key, &nonce, &msg);
+
+        let cs = ConstraintSystem::new_ref();
+        let key_var = FpVar::new_witness(cs.clone(), || Ok(key))?;
+        let nonce_var = FpVar::new_witness(cs.clone(), || Ok(nonce))?;
+        let ct_var = PoseidonCiphertextVar::new_witness(cs.clone(), || Ok(&ct))?;
+        let decrypted = Bls12PoseidonCipher::decrypt_gadget(&key_var, &nonce_var, &ct_var)?;
+
+        assert_eq!(decrypted.value()?, msg);
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        let proof = create_new_proof(4);
+
+        let cs = ConstraintSystem::new_ref();
+        proof.clone().generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+        assert_eq!(cs.num_instance_variables(), 1 + proof.public_inputs().len());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a ciphertext of a different vector does not satisfy the circuit
+    fn cs_wrong_ciphertext() -> Result<()> {
+        let mut proof = create_new_proof(4);
+        proof.ciphertext = Bls12PoseidonCipher::encrypt(&proof.key, &proof.nonce, &random_elems(4));
+
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+
+        assert!(!cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a true proof is valid, and that it doesn't verify for a modified ciphertext
+    fn pf_complete_and_sound() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let proof = create_new_proof(3);
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(proof.clone(), &mut rng)?;
+        let pf = Groth16::<Bls12_381>::prove(&pk, proof.clone(), &mut rng)?;
+
+        let public_inputs = proof.public_inputs();
+        assert!(Groth16::<Bls12_381>::verify(&vk, &public_inputs, &pf)?);
+
+        let mut bad_inputs = public_inputs;
+        bad_inputs[3] += Fr::from(1u8);
+        assert!(!Groth16::<Bls12_381>::verify(&vk, &bad_inputs, &pf)?);
+
+        Ok(())
+    }
+}
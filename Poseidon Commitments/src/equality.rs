@@ -0,0 +1,162 @@
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::Error as ArkError;
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef};
+use rand::Rng;
+
+use crate::{commit_gadget, create_new_commitment, CommitmentProof};
+
+/// Proves that two public commitments, made with [`crate::commit`], open to the same hidden
+/// value. The public inputs are the two commitments, in order.
+#[derive(Clone)]
+pub struct EqualityProof {
+    // Public inputs
+    pub commitments: [Fr; 2],
+
+    // Private witnesses
+    pub value: Fr,
+    pub com_rnds: [Fr; 2],
+}
+
+impl EqualityProof {
+    pub fn new(value: Fr, com_rnds: [Fr; 2], commitments: [Fr; 2]) -> Self {
+        Self {
+            commitments,
+            value,
+            com_rnds,
+        }
+    }
+
+    /// Commits to the value of `opening` again, with fresh randomness. Returns the new opening,
+    /// and the statement that links it to the old commitment without revealing the value.
+    pub fn rerandomize<R: Rng>(
+        opening: &CommitmentProof,
+        rng: &mut R,
+    ) -> Result<(CommitmentProof, Self), ArkError> {
+        let fresh = create_new_commitment(opening.value, rng)?;
+        let proof = Self::new(
+            opening.value,
+            [opening.cmd_rnd, fresh.cmd_rnd],
+            [opening.commitment, fresh.commitment],
+        );
+
+        Ok((fresh, proof))
+    }
+
+    /// The public inputs a verifier needs to check a proof of this statement
+    pub fn public_inputs(&self) -> Vec<Fr> {
+        self.commitments.to_vec()
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for EqualityProof {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
+        let public_commitments = self
+            .commitments
+            .iter()
+            .map(|c| FpVar::new_input(ark_relations::ns!(cs, "pub commitment"), || Ok(c)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // There's a single value witness, so both commitments have to open to it
+        let value = FpVar::new_witness(ark_relations::ns!(cs, "value"), || Ok(self.value))?;
+        for (rnd, public_commitment) in self.com_rnds.iter().zip(&public_commitments) {
+            let r = FpVar::new_witness(ark_relations::ns!(cs, "com_rnd"), || Ok(rnd))?;
+            commit_gadget(&value, &r)?.enforce_equal(public_commitment)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commit;
+    use ark_bls12_381::Bls12_381;
+    use ark_ff::UniformRand;
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_snark::SNARK;
+
+    fn create_new_proof() -> EqualityProof {
+        let mut rng = rand::thread_rng();
+        let opening = create_new_commitment(Fr::rand(&mut rng), &mut rng).unwrap();
+
+        EqualityProof::rerandomize(&opening, &mut rng).unwrap().1
+    }
+
+    #[test]
+    fn rerandomize() {
+        let mut rng = rand::thread_rng();
+        let opening = create_new_commitment(Fr::from(3u8), &mut rng).unwrap();
+        let (fresh, proof) = EqualityProof::rerandomize(&opening, &mut rng).unwrap();
+
+        assert_eq!(fresh.value, opening.value);
+        assert_ne!(fresh.commitment, opening.commitment);
+        assert_eq!(
+            fresh.commitment,
+            commit(&fresh.value, &fresh.cmd_rnd).unwrap()
+        );
+        assert_eq!(
+            proof.public_inputs(),
+            [opening.commitment, fresh.commitment]
+        );
+    }
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        let cs = ConstraintSystem::new_ref();
+        create_new_proof().generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that commitments to different values don't satisfy the circuit, whichever value is
+    /// used as the witness
+    fn cs_different_values() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (Fr::from(3u8), Fr::from(4u8));
+        let com_a = create_new_commitment(a, &mut rng).unwrap();
+        let com_b = create_new_commitment(b, &mut rng).unwrap();
+
+        for value in [a, b] {
+            let proof = EqualityProof::new(
+                value,
+                [com_a.cmd_rnd, com_b.cmd_rnd],
+                [com_a.commitment, com_b.commitment],
+            );
+
+            let cs = ConstraintSystem::new_ref();
+            proof.generate_constraints(cs.clone())?;
+
+            assert!(!cs.is_satisfied()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a true proof is valid, and doesn't verify against other commitments
+    fn pf_complete_and_sound() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let proof = create_new_proof();
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(proof.clone(), &mut rng)?;
+        let pf = Groth16::<Bls12_381>::prove(&pk, proof.clone(), &mut rng)?;
+
+        assert!(Groth16::<Bls12_381>::verify(
+            &vk,
+            &proof.public_inputs(),
+            &pf
+        )?);
+
+        let other = create_new_proof();
+        let mixed = [proof.commitments[0], other.commitments[1]];
+        assert!(!Groth16::<Bls12_381>::verify(&vk, &mixed, &pf)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use ark_bls12_381::Fr as BlsFr;
+use ark_crypto_primitives::Error as ArkError;
+use ark_ff::{PrimeField, ToConstraintField};
+use ark_r1cs_std::{
+    fields::{fp::FpVar, FieldVar},
+    uint8::UInt8,
+    R1CSVar, ToConstraintFieldGadget,
+};
+use ark_relations::r1cs::SynthesisError;
+use sha2::{Digest, Sha256};
+
+use crate::poseidon::{poseidon_iterated_hash, poseidon_iterated_hash_gadget};
+
+// The input block size and output size of SHA-256
+const SHA256_BLOCK_BYTES: usize = 64;
+const SHA256_OUTPUT_BYTES: usize = 32;
+// ceil((ceil(log2(p)) + k) / 8) for the BLS12-381 scalar field and k = 128 bits of security
+const FR_HASH_BYTES: usize = (BlsFr::MODULUS_BIT_SIZE as usize + 128).div_ceil(8);
+const H2F_DOMAIN_SEP: &[u8] = b"ph2f";
+
+/// expand_message_xmd from RFC 9380, section 5.3.1, with SHA-256. Expands `msg` into
+/// `len_in_bytes` uniformly random bytes, domain-separated by `dst`.
+///
+/// ark-ff 0.4 has this too, but its `DefaultFieldHasher` pads with the wrong block size, so its
+/// output doesn't match the RFC.
+pub fn expand_message_xmd(
+    msg: &[u8],
+    dst: &[u8],
+    len_in_bytes: usize,
+) -> Result<Vec<u8>, ArkError> {
+    let ell = len_in_bytes.div_ceil(SHA256_OUTPUT_BYTES);
+    if ell > 255 || len_in_bytes > u16::MAX as usize {
+        return Err(format!("cannot expand a message to {len_in_bytes} bytes").into());
+    }
+
+    // DSTs longer than 255 bytes are hashed down first, see section 5.3.3
+    let long_dst;
+    let dst = if dst.len() > 255 {
+        long_dst = Sha256::new()
+            .chain_update(b"H2C-OVERSIZE-DST-")
+            .chain_update(dst)
+            .finalize();
+        &long_dst[..]
+    } else {
+        dst
+    };
+    let dst_prime = [dst, &[dst.len() as u8]].concat();
+
+    let b_0 = Sha256::new()
+        .chain_update([0u8; SHA256_BLOCK_BYTES])
+        .chain_update(msg)
+        .chain_update((len_in_bytes as u16).to_be_bytes())
+        .chain_update([0u8])
+        .chain_update(&dst_prime)
+        .finalize();
+
+    let mut uniform_bytes = Vec::with_capacity(ell * SHA256_OUTPUT_BYTES);
+    let mut b_i = Sha256::new()
+        .chain_update(b_0)
+        .chain_update([1u8])
+        .chain_update(&dst_prime)
+        .finalize();
+    uniform_bytes.extend_from_slice(&b_i);
+    for i in 2..=ell {
+        let xored: Vec<u8> = b_0.iter().zip(&b_i).map(|(x, y)| x ^ y).collect();
+        b_i = Sha256::new()
+            .chain_update(xored)
+            .chain_update([i as u8])
+            .chain_update(&dst_prime)
+            .finalize();
+        uniform_bytes.extend_from_slice(&b_i);
+    }
+
+    uniform_bytes.truncate(len_in_bytes);
+    Ok(uniform_bytes)
+}
+
+/// hash_to_field from RFC 9380, section 5.2, for the BLS12-381 scalar field with
+/// expand_message_xmd and SHA-256. Outputs `count` field elements.
+pub fn hash_to_field(msg: &[u8], dst: &[u8], count: usize) -> Result<Vec<BlsFr>, ArkError> {
+    let uniform_bytes = expand_message_xmd(msg, dst, count * FR_HASH_BYTES)?;
+
+    Ok(uniform_bytes
+        .chunks(FR_HASH_BYTES)
+        .map(BlsFr::from_be_bytes_mod_order)
+        .collect())
+}
+
+// The domain separator, then the DST and the message, each preceded by its length in bytes
+fn poseidon_hash_input(dst: &[u8], msg_len: usize) -> Vec<BlsFr> {
+    let packed_dst: Vec<BlsFr> = dst
+        .to_field_elements()
+        .expect("bytes always pack into field elements");
+    [
+        vec![
+            BlsFr::from_le_bytes_mod_order(H2F_DOMAIN_SEP),
+            BlsFr::from(dst.len() as u64),
+        ],
+        packed_dst,
+        vec![BlsFr::from(msg_len as u64)],
+    ]
+    .concat()
+}
+
+/// A SNARK-friendly counterpart to [`hash_to_field`], for when the message has to be hashed
+/// in-circuit. It hashes the DST and the message, packed into field elements, with Poseidon. The
+/// output is a different field element than [`hash_to_field`]'s.
+pub fn poseidon_hash_to_field(msg: &[u8], dst: &[u8]) -> Result<BlsFr, ArkError> {
+    let packed_msg: Vec<BlsFr> = msg
+        .to_field_elements()
+        .expect("bytes always pack into field elements");
+
+    poseidon_iterated_hash(&[poseidon_hash_input(dst, msg.len()), packed_msg].concat())
+}
+
+/// The gadget version of [`poseidon_hash_to_field`]. The DST is a constant, and the message
+/// length is fixed by the circuit.
+pub fn poseidon_hash_to_field_gadget(
+    msg: &[UInt8<BlsFr>],
+    dst: &[u8],
+) -> Result<FpVar<BlsFr>, SynthesisError> {
+    let cs = msg.cs();
+    let header = poseidon_hash_input(dst, msg.len())
+        .into_iter()
+        .map(FpVar::constant);
+    let packed_msg: Vec<FpVar<BlsFr>> = msg.to_constraint_field()?;
+
+    poseidon_iterated_hash_gadget(&cs, &header.chain(packed_msg).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ff::MontFp;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use serde::Deserialize;
+
+    const DST: &[u8] = b"QUUX-V01-CS02-with-expander-SHA256-128";
+
+    #[derive(Deserialize)]
+    struct TestVectors {
+        suites: Vec<Suite>,
+    }
+
+    #[derive(Deserialize)]
+    struct Suite {
+        dst: String,
+        tests: Vec<XmdVector>,
+    }
+
+    #[derive(Deserialize)]
+    struct XmdVector {
+        msg: String,
+        len_in_bytes: usize,
+        uniform_bytes: String,
+    }
+
+    #[test]
+    fn expand_message_xmd_kat() {
+        let vectors: TestVectors =
+            serde_json::from_str(include_str!("../test_vectors/expand_message_xmd.json")).unwrap();
+
+        for suite in vectors.suites {
+            for v in suite.tests {
+                let out =
+                    expand_message_xmd(v.msg.as_bytes(), suite.dst.as_bytes(), v.len_in_bytes)
+                        .unwrap();
+                assert_eq!(
+                    hex::encode(out),
+                    v.uniform_bytes,
+                    "msg = {:?}, dst length = {}",
+                    v.msg,
+                    suite.dst.len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn expand_message_xmd_too_long() {
+        assert!(expand_message_xmd(b"abc", DST, 255 * 32).is_ok());
+        assert!(expand_message_xmd(b"abc", DST, 255 * 32 + 1).is_err());
+    }
+
+    #[test]
+    /// The RFC has no vectors for the scalar field, so these come from an independent
+    /// implementation
+    fn hash_to_field_kat() {
+        assert_eq!(
+            hash_to_field(b"", DST, 2).unwrap(),
+            [
+                MontFp!(
+                    "28278357814166947568488584426491389479439390112357611701312376267537105050041"
+                ),
+                MontFp!(
+                    "49308309320967913505919568061414385396948656328546067415362443647623667680601"
+                ),
+            ]
+        );
+        assert_eq!(
+            hash_to_field(b"abc", DST, 2).unwrap(),
+            [
+                MontFp!(
+                    "8806368777326611730071432931469475601663107643044540149455546132280892900883"
+                ),
+                MontFp!(
+                    "330432291611276014638773701200383811214971228698308470022688781867633840072"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    /// Check that the DST and the message length are bound by the Poseidon hash
+    fn poseidon_hash_to_field_binds_inputs() {
+        let expected = poseidon_hash_to_field(b"alice", b"usernames").unwrap();
+
+        assert_ne!(
+            poseidon_hash_to_field(b"alice", b"documents").unwrap(),
+            expected
+        );
+        assert_ne!(
+            poseidon_hash_to_field(b"alice\0", b"usernames").unwrap(),
+            expected
+        );
+        assert_ne!(
+            poseidon_hash_to_field(b"", b"usernamesalice").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn poseidon_gadget_matches_native() -> Result<()> {
+        for len in [0, 1, 31, 32, 100] {
+            let msg: Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+            let cs = ConstraintSystem::new_ref();
+            let msg_var = UInt8::new_witness_vec(cs.clone(), &msg)?;
+            let hash = poseidon_hash_to_field_gadget(&msg_var, DST)?;
+
+            assert_eq!(
+                hash.value()?,
+                poseidon_hash_to_field(&msg, DST).unwrap(),
+                "len = {len}"
+            );
+            assert!(cs.is_satisfied()?);
+        }
+
+        Ok(())
+    }
+}
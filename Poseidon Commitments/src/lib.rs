@@ -0,0 +1,254 @@
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::commitment::{CommitmentGadget, CommitmentScheme};
+use ark_crypto_primitives::Error as ArkError;
+use ark_ff::{BigInteger, PrimeField, UniformRand};
+use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, R1CSVar, ToBytesGadget};
+use ark_relations::r1cs::{ConstraintSynthesizer, SynthesisError};
+use rand::Rng;
+
+#[cfg(test)]
+mod constraint_budgets;
+pub mod encryption;
+pub mod equality;
+pub mod hash_to_field;
+pub mod merkle;
+pub mod mimc;
+pub mod pedersen;
+pub mod poseidon;
+pub mod poseidon2;
+mod poseidon2_constants;
+pub mod rescue;
+mod round_constants;
+pub mod set_membership;
+pub mod transcript;
+pub mod vector_commitment;
+use poseidon::{Bls12PoseidonCommitter, UnitVar};
+
+pub type Commitment = <Bls12PoseidonCommitter as CommitmentScheme>::Output;
+pub type Randomness = <Bls12PoseidonCommitter as CommitmentScheme>::Randomness;
+
+/// Proves knowledge of the `value` and randomness that open a public `commitment`
+#[derive(Clone)]
+pub struct CommitmentProof {
+    pub cmd_rnd: Randomness,
+    pub value: Fr,
+    pub commitment: Commitment
+}
+
+impl CommitmentProof {
+    pub fn new(value: Fr, rnd: Fr, commitment: Fr) -> Self {
+        Self { cmd_rnd: rnd, value, commitment }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for CommitmentProof {
+    fn generate_constraints(self, cs: ark_relations::r1cs::ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
+        let public_commitment = FpVar::new_input(ark_relations::ns!(cs, "pub commitment"), || Ok(self.commitment))?;
+
+        let r = FpVar::new_witness(ark_relations::ns!(cs, "com_rnd"), || Ok(self.cmd_rnd))?;
+        let value = FpVar::new_witness(ark_relations::ns!(cs, "value"), || Ok(self.value))?;
+
+        let final_commitment = commit_gadget(&value, &r)?;
+
+        final_commitment.enforce_equal(&public_commitment)?;
+
+        Ok(())
+    }
+}
+
+/// The gadget version of [`commit`]
+pub fn commit_gadget(value: &FpVar<Fr>, rnd: &FpVar<Fr>) -> Result<FpVar<Fr>, SynthesisError> {
+    let params = UnitVar::new_constant(ark_relations::ns!(value.cs(), "poseidon_params"), ())?;
+    let input = value.to_bytes()?;
+    <Bls12PoseidonCommitter as CommitmentGadget<Bls12PoseidonCommitter, Fr>>::commit(&params, &input, rnd)
+}
+
+/// Commits to the byte encoding of `value`, which is what [`CommitmentProof`] opens in-circuit
+pub fn commit(value: &Fr, rnd: &Randomness) -> Result<Commitment, ArkError> {
+    let input = value.into_bigint().to_bytes_le();
+    <Bls12PoseidonCommitter as CommitmentScheme>::commit(&(), &input, rnd)
+}
+
+/// Commits to `value` with fresh randomness
+pub fn create_new_commitment<R: Rng>(value: Fr, rng: &mut R) -> Result<CommitmentProof, ArkError> {
+    let rnd = Fr::rand(rng);
+    let commitment = commit(&value, &rnd)?;
+
+    Ok(CommitmentProof::new(value, rnd, commitment))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_bls12_381::Bls12_381;
+    use ark_groth16::Groth16;
+    use ark_ff::UniformRand;
+    use ark_groth16::{Proof, ProvingKey, VerifyingKey};
+    use ark_relations::r1cs::OptimizationGoal;
+    use ark_snark::SNARK;
+    use proptest::prelude::*;
+    use std::sync::OnceLock;
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let proof = create_new_commitment(3u8.into(), &mut rng).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a false proof is invalid
+    fn pf_sound() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let p1 = create_new_commitment(3u8.into(), &mut rng).unwrap();
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(p1.clone(), &mut rng)?;
+        let proof = Groth16::<Bls12_381>::prove(&pk, p1, &mut rng)?;
+
+        let p2 = create_new_commitment(3u8.into(), &mut rng).unwrap();
+        let public_inputs = [p2.commitment];
+        let is_valid = Groth16::<Bls12_381>::verify(&vk, &public_inputs, &proof)?;
+
+        assert!(!is_valid);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that values using all 32 bytes of their encoding can be opened
+    fn cs_sat_large_value() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let proof = create_new_commitment(Fr::rand(&mut rng), &mut rng).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a true proof is valid
+    fn pf_complete() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let rnd = Fr::rand(&mut rng);
+        let value = 3;
+        let commitment = <Bls12PoseidonCommitter as CommitmentScheme>::commit(&(), &[value], &rnd).unwrap();
+
+        let v = CommitmentProof::new(value.into(), rnd, commitment);
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(v.clone(), &mut rng)?;
+
+        let proof = Groth16::<Bls12_381>::prove(&pk, v.clone(), &mut rng)?;
+
+        let public_inputs = [v.commitment];
+        let is_valid = Groth16::<Bls12_381>::verify(&vk, &public_inputs, &proof)?;
+
+        assert!(is_valid);
+
+        Ok(())
+    }
+
+    fn arb_fr() -> impl Strategy<Value = Fr> {
+        any::<[u8; 32]>().prop_map(|bytes| Fr::from_le_bytes_mod_order(&bytes))
+    }
+
+    // Offsets to add to the value, randomness and commitment of an opening, at least one of which
+    // is nonzero
+    fn arb_mutation() -> impl Strategy<Value = [Option<Fr>; 3]> {
+        let offset = || prop::option::of(arb_fr().prop_filter("nonzero", |x| *x != Fr::from(0u8)));
+        [offset(), offset(), offset()]
+            .prop_filter("mutate something", |m| m.iter().any(Option::is_some))
+    }
+
+    fn mutate(opening: &CommitmentProof, mutation: &[Option<Fr>; 3]) -> CommitmentProof {
+        let shift = |x: Fr, offset: Option<Fr>| x + offset.unwrap_or_default();
+        CommitmentProof::new(
+            shift(opening.value, mutation[0]),
+            shift(opening.cmd_rnd, mutation[1]),
+            shift(opening.commitment, mutation[2]),
+        )
+    }
+
+    // The circuit doesn't depend on the opening, so all cases can share one set of keys
+    fn keys() -> &'static (ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>) {
+        static KEYS: OnceLock<(ProvingKey<Bls12_381>, VerifyingKey<Bls12_381>)> = OnceLock::new();
+        KEYS.get_or_init(|| {
+            let blank = CommitmentProof::new(Fr::from(0u8), Fr::from(0u8), Fr::from(0u8));
+            Groth16::<Bls12_381>::circuit_specific_setup(blank, &mut rand::thread_rng()).unwrap()
+        })
+    }
+
+    proptest! {
+        /// Check that no inconsistent opening satisfies the circuit
+        #[test]
+        fn cs_rejects_mutations(value in arb_fr(), mutation in arb_mutation()) {
+            let mut rng = rand::thread_rng();
+            let opening = create_new_commitment(value, &mut rng).unwrap();
+            let bad = mutate(&opening, &mutation);
+            prop_assume!(commit(&bad.value, &bad.cmd_rnd).unwrap() != bad.commitment);
+
+            let cs = ConstraintSystem::new_ref();
+            bad.generate_constraints(cs.clone()).unwrap();
+
+            prop_assert!(!cs.is_satisfied().unwrap());
+        }
+    }
+
+    // Groth16::prove asserts that the circuit is satisfied in debug builds, so proofs from
+    // inconsistent openings are made from the constraint matrices directly
+    fn prove_unchecked(
+        pk: &ProvingKey<Bls12_381>,
+        circuit: CommitmentProof,
+        rng: &mut impl rand::Rng,
+    ) -> Proof<Bls12_381> {
+        let cs = ConstraintSystem::new_ref();
+        cs.set_optimization_goal(OptimizationGoal::Constraints);
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.finalize();
+
+        let matrices = cs.to_matrices().unwrap();
+        let cs = cs.borrow().unwrap();
+        let assignment = [&cs.instance_assignment[..], &cs.witness_assignment[..]].concat();
+        Groth16::<Bls12_381>::create_proof_with_reduction_and_matrices(
+            pk,
+            Fr::rand(rng),
+            Fr::rand(rng),
+            &matrices,
+            cs.num_instance_variables,
+            cs.num_constraints,
+            &assignment,
+        )
+        .unwrap()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        /// Check that Groth16 rejects proofs made from inconsistent openings, and honest proofs
+        /// checked against a different commitment
+        #[test]
+        fn pf_rejects_mutations(value in arb_fr(), mutation in arb_mutation()) {
+            let mut rng = rand::thread_rng();
+            let (pk, vk) = keys();
+            let opening = create_new_commitment(value, &mut rng).unwrap();
+            let bad = mutate(&opening, &mutation);
+            prop_assume!(commit(&bad.value, &bad.cmd_rnd).unwrap() != bad.commitment);
+
+            let bad_proof = prove_unchecked(pk, bad.clone(), &mut rng);
+            prop_assert!(!Groth16::<Bls12_381>::verify(vk, &[bad.commitment], &bad_proof).unwrap());
+
+            if bad.commitment != opening.commitment {
+                let proof = Groth16::<Bls12_381>::prove(pk, opening, &mut rng).unwrap();
+                prop_assert!(!Groth16::<Bls12_381>::verify(vk, &[bad.commitment], &proof).unwrap());
+            }
+        }
+    }
+}
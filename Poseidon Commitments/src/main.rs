@@ -1,116 +1,230 @@
-use ark_bls12_381::Fr;
-use ark_crypto_primitives::commitment::{CommitmentGadget, CommitmentScheme};
-use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar, ToBytesGadget};
-use ark_relations::r1cs::ConstraintSynthesizer;
-use ark_ff::UniformRand;
-
-mod poseidon;
-use poseidon::{Bls12PoseidonCommitter, UnitVar};
-
-pub type Commitment = <Bls12PoseidonCommitter as CommitmentScheme>::Output;
-pub type Randomness = <Bls12PoseidonCommitter as CommitmentScheme>::Randomness;
-
-#[derive(Clone)]
-struct CommitmentProof {
-    cmd_rnd: Randomness,
-    value: Fr,
-    commitment: Commitment
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_crypto_primitives::Error as ArkError;
+use ark_ff::Zero;
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use poseidon::{commit, create_new_commitment, CommitmentProof};
+
+const USAGE: &str = "usage: poseidon [-d <dir>] <command>
+
+commands:
+    commit <value>  commit to <value>, writing value.bin, randomness.bin and commitment.bin
+    open            check that value.bin and randomness.bin open commitment.bin
+    prove           prove knowledge of the opening of commitment.bin, writing proof.bin
+                    (generates proving_key.bin and verifying_key.bin if they don't exist yet)
+    verify          check proof.bin against commitment.bin and verifying_key.bin
+
+options:
+    -d <dir>        read and write files in <dir> instead of the current directory";
+
+/// The files the CLI reads and writes, all in the same directory
+struct Files {
+    dir: PathBuf,
 }
 
-impl CommitmentProof {
-    pub fn new(value: Fr, rnd: Fr, commitment: Fr) -> Self {
-        Self { cmd_rnd: rnd, value, commitment }
+impl Files {
+    fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
     }
-}
 
-impl ConstraintSynthesizer<Fr> for CommitmentProof {
-    fn generate_constraints(self, cs: ark_relations::r1cs::ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
-        let public_commitment = FpVar::new_input(ark_relations::ns!(cs, "pub commitment"), || Ok(self.commitment))?;
+    fn value(&self) -> PathBuf {
+        self.dir.join("value.bin")
+    }
 
-        let r = FpVar::new_witness(ark_relations::ns!(cs, "com_rnd"), || Ok(self.cmd_rnd))?;
-        let value = FpVar::new_witness(ark_relations::ns!(cs, "value"), || Ok(self.value))?;
+    fn randomness(&self) -> PathBuf {
+        self.dir.join("randomness.bin")
+    }
 
-        let params = UnitVar::new_constant(ark_relations::ns!(cs, "poseidon_params"), ())?;
-        let input = value.to_bytes()?;
-        let final_commitment = <Bls12PoseidonCommitter as CommitmentGadget<Bls12PoseidonCommitter, Fr>>::commit(&params, &input, &r)?;
+    fn commitment(&self) -> PathBuf {
+        self.dir.join("commitment.bin")
+    }
 
-        final_commitment.enforce_equal(&public_commitment)?;
+    fn proving_key(&self) -> PathBuf {
+        self.dir.join("proving_key.bin")
+    }
 
-        Ok(())
+    fn verifying_key(&self) -> PathBuf {
+        self.dir.join("verifying_key.bin")
     }
+
+    fn proof(&self) -> PathBuf {
+        self.dir.join("proof.bin")
+    }
+}
+
+fn write_to<T: CanonicalSerialize>(path: &Path, val: &T) -> Result<(), ArkError> {
+    let file = File::create(path).map_err(|e| format!("could not create {path:?}: {e}"))?;
+    val.serialize_compressed(BufWriter::new(file))?;
+    Ok(())
+}
+
+fn read_from<T: CanonicalDeserialize>(path: &Path) -> Result<T, ArkError> {
+    let file = File::open(path).map_err(|e| format!("could not open {path:?}: {e}"))?;
+    Ok(T::deserialize_compressed(BufReader::new(file))?)
+}
+
+/// Reads the opening of the commitment, as written by [`cmd_commit`]
+fn read_opening(files: &Files) -> Result<CommitmentProof, ArkError> {
+    let value = read_from(&files.value())?;
+    let rnd = read_from(&files.randomness())?;
+    let commitment = read_from(&files.commitment())?;
+
+    Ok(CommitmentProof::new(value, rnd, commitment))
 }
 
-fn create_new_commitment() -> CommitmentProof {
+fn cmd_commit(files: &Files, value: Fr) -> Result<Fr, ArkError> {
     let mut rng = rand::thread_rng();
-    let rnd = Fr::rand(&mut rng);
-    let value = 3;
-    let commitment = <Bls12PoseidonCommitter as CommitmentScheme>::commit(&(), &[value], &rnd).unwrap();
+    let opening = create_new_commitment(value, &mut rng)?;
+
+    write_to(&files.value(), &opening.value)?;
+    write_to(&files.randomness(), &opening.cmd_rnd)?;
+    write_to(&files.commitment(), &opening.commitment)?;
 
-    CommitmentProof::new(value.into(), rnd, commitment)
+    Ok(opening.commitment)
 }
 
-fn main() {
+fn cmd_open(files: &Files) -> Result<bool, ArkError> {
+    let opening = read_opening(files)?;
 
+    Ok(commit(&opening.value, &opening.cmd_rnd)? == opening.commitment)
+}
+
+fn cmd_prove(files: &Files) -> Result<(), ArkError> {
+    let mut rng = rand::thread_rng();
+    let opening = read_opening(files)?;
+
+    // The circuit doesn't depend on the opening, so the keys can be reused across commitments
+    let pk: ProvingKey<Bls12_381> = if files.proving_key().exists() {
+        read_from(&files.proving_key())?
+    } else {
+        let blank = CommitmentProof::new(Fr::zero(), Fr::zero(), Fr::zero());
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(blank, &mut rng)?;
+        write_to(&files.proving_key(), &pk)?;
+        write_to(&files.verifying_key(), &vk)?;
+        pk
+    };
+
+    let proof = Groth16::<Bls12_381>::prove(&pk, opening, &mut rng)?;
+    write_to(&files.proof(), &proof)
+}
+
+fn cmd_verify(files: &Files) -> Result<bool, ArkError> {
+    let vk: VerifyingKey<Bls12_381> = read_from(&files.verifying_key())?;
+    let commitment: Fr = read_from(&files.commitment())?;
+    let proof: Proof<Bls12_381> = read_from(&files.proof())?;
+
+    Ok(Groth16::<Bls12_381>::verify(&vk, &[commitment], &proof)?)
+}
+
+fn run() -> Result<(), ArkError> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut dir = PathBuf::from(".");
+    if args.first().map(String::as_str) == Some("-d") {
+        if args.len() < 2 {
+            return Err(USAGE.into());
+        }
+        dir = PathBuf::from(args.remove(1));
+        args.remove(0);
+    }
+    let files = Files::new(dir);
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        ["commit", value] => {
+            let value = Fr::from_str(value).map_err(|_| format!("invalid value: {value}"))?;
+            let commitment = cmd_commit(&files, value)?;
+            println!("{commitment}");
+        }
+        ["open"] => {
+            if !cmd_open(&files)? {
+                return Err("the value and randomness do not open the commitment".into());
+            }
+            println!("valid opening");
+        }
+        ["prove"] => {
+            cmd_prove(&files)?;
+            println!("wrote {:?}", files.proof());
+        }
+        ["verify"] => {
+            if !cmd_verify(&files)? {
+                return Err("invalid proof".into());
+            }
+            println!("valid proof");
+        }
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    // use ark_crypto_primitives::CommitmentScheme;
     use ark_ff::UniformRand;
-    use ark_relations::r1cs::{ConstraintSystem, Result};
-    use ark_bls12_381::Bls12_381;
-    use ark_groth16::Groth16;
-    use ark_snark::SNARK;
 
-    #[test]
-    fn cs_sat() -> Result<()> {
-        let proof = create_new_commitment();
-
-        let cs = ConstraintSystem::new_ref();
-        proof.generate_constraints(cs.clone())?;
-
-        assert!(cs.is_satisfied()?);
-
-        Ok(())
+    fn temp_files(name: &str) -> Files {
+        let dir = std::env::temp_dir().join(format!("poseidon-cli-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Files::new(dir)
     }
 
     #[test]
-    /// Check that a false proof is invalid
-    fn pf_sound() -> Result<()> {
+    fn commit_prove_verify() -> Result<(), ArkError> {
         let mut rng = rand::thread_rng();
-        let p1 = create_new_commitment();
+        let files = temp_files("roundtrip");
 
-        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(p1.clone(), &mut rng)?;
-        let proof = Groth16::prove(&pk, p1, &mut rng)?;
+        let commitment = cmd_commit(&files, Fr::rand(&mut rng))?;
+        assert_eq!(read_from::<Fr>(&files.commitment())?, commitment);
+        assert!(cmd_open(&files)?);
 
-        let p2 = create_new_commitment();
-        let public_inputs = [p2.commitment];
-        let is_valid = Groth16::<Bls12_381>::verify(&vk, &public_inputs, &proof)?;
+        cmd_prove(&files)?;
+        assert!(cmd_verify(&files)?);
 
-        assert!(!is_valid);
+        // The keys are reused for a new commitment, and the old proof doesn't verify against it
+        cmd_commit(&files, 3u8.into())?;
+        assert!(!cmd_verify(&files)?);
+        cmd_prove(&files)?;
+        assert!(cmd_verify(&files)?);
 
+        std::fs::remove_dir_all(&files.dir)?;
         Ok(())
     }
 
     #[test]
-    /// Check that a true proof is valid
-    fn pf_complete() -> Result<()> {
-        let mut rng = rand::thread_rng();
-        let rnd = Fr::rand(&mut rng);
-        let value = 3;
-        let commitment = <Bls12PoseidonCommitter as CommitmentScheme>::commit(&(), &[value], &rnd).unwrap();
+    fn open_wrong_value() -> Result<(), ArkError> {
+        let files = temp_files("open");
 
-        let v = CommitmentProof::new(value.into(), rnd, commitment);
-        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(v.clone(), &mut rng)?;
+        cmd_commit(&files, 3u8.into())?;
+        write_to(&files.value(), &Fr::from(4u8))?;
+        assert!(!cmd_open(&files)?);
 
-        let proof = Groth16::prove(&pk, v.clone(), &mut rng)?;
+        std::fs::remove_dir_all(&files.dir)?;
+        Ok(())
+    }
 
-        let public_inputs = [v.commitment];
-        let is_valid = Groth16::<Bls12_381>::verify(&vk, &public_inputs, &proof)?;
+    #[test]
+    fn missing_files() {
+        let files = temp_files("missing");
 
-        assert!(is_valid);
+        assert!(cmd_open(&files).is_err());
+        assert!(cmd_verify(&files).is_err());
 
-        Ok(())
+        std::fs::remove_dir_all(&files.dir).unwrap();
     }
 }
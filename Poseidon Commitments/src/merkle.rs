@@ -0,0 +1,323 @@
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::{
+    crh::{TwoToOneCRHScheme, TwoToOneCRHSchemeGadget},
+    Error as ArkError,
+};
+use ark_ff::Zero;
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    boolean::Boolean,
+    eq::EqGadget,
+    fields::fp::FpVar,
+    select::CondSelectGadget,
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, Namespace, SynthesisError};
+use std::borrow::Borrow;
+
+use crate::poseidon::{Bls12PoseidonCrh, UnitVar};
+
+/// A fixed-height binary Merkle tree over BLS12-381 scalars, whose inner nodes are computed with
+/// [`TwoToOneCRHScheme::compress`] for [`Bls12PoseidonCrh`]. Leaves are stored as-is, so they should already be
+/// hashes or commitments. Unused leaves are filled with zero.
+#[derive(Clone, Debug)]
+pub struct PoseidonMerkleTree {
+    /// `layers[0]` holds the leaves and `layers[height]` holds the root
+    layers: Vec<Vec<Fr>>,
+}
+
+impl PoseidonMerkleTree {
+    /// Builds a tree with `2^height` leaves, the first of which are `leaves`
+    pub fn new(leaves: &[Fr], height: usize) -> Result<Self, ArkError> {
+        let num_leaves = 1usize
+            .checked_shl(height as u32)
+            .ok_or("tree height is too large")?;
+        if leaves.len() > num_leaves {
+            return Err(format!(
+                "cannot fit {} leaves in a tree of height {height}",
+                leaves.len()
+            )
+            .into());
+        }
+
+        let mut bottom = leaves.to_vec();
+        bottom.resize(num_leaves, Fr::zero());
+
+        let mut layers = vec![bottom];
+        for _ in 0..height {
+            let below = layers.last().unwrap();
+            let layer = below
+                .chunks(2)
+                .map(|pair| {
+                    <Bls12PoseidonCrh as TwoToOneCRHScheme>::compress(&(), pair[0], pair[1])
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            layers.push(layer);
+        }
+
+        Ok(Self { layers })
+    }
+
+    pub fn height(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn root(&self) -> Fr {
+        self.layers[self.height()][0]
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<Fr> {
+        self.layers[0].get(index).copied()
+    }
+
+    /// Returns the authentication path for the leaf at `index`
+    pub fn generate_proof(&self, index: usize) -> Result<PoseidonPath, ArkError> {
+        if index >= self.layers[0].len() {
+            return Err(format!("leaf index {index} is out of bounds").into());
+        }
+
+        let siblings = self.layers[..self.height()]
+            .iter()
+            .enumerate()
+            .map(|(level, layer)| layer[(index >> level) ^ 1])
+            .collect();
+
+        Ok(PoseidonPath {
+            leaf_index: index,
+            siblings,
+        })
+    }
+}
+
+/// A path from a leaf up to the root of a [`PoseidonMerkleTree`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoseidonPath {
+    pub leaf_index: usize,
+    /// `siblings[i]` is the sibling of the node at level `i` on the path, from the bottom up
+    pub siblings: Vec<Fr>,
+}
+
+impl PoseidonPath {
+    /// Hashes `leaf` up along the path
+    pub fn compute_root(&self, leaf: &Fr) -> Result<Fr, ArkError> {
+        let mut curr = *leaf;
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            curr = if (self.leaf_index >> level) & 1 == 0 {
+                <Bls12PoseidonCrh as TwoToOneCRHScheme>::compress(&(), curr, *sibling)?
+            } else {
+                <Bls12PoseidonCrh as TwoToOneCRHScheme>::compress(&(), *sibling, curr)?
+            };
+        }
+
+        Ok(curr)
+    }
+
+    /// Checks that `leaf` sits at this path's position in the tree with the given `root`
+    pub fn verify(&self, root: &Fr, leaf: &Fr) -> Result<bool, ArkError> {
+        Ok(self.compute_root(leaf)? == *root)
+    }
+}
+
+/// The gadget version of [`PoseidonPath`]
+pub struct PoseidonPathVar {
+    /// `is_right[i]` is true iff the node at level `i` on the path is a right child
+    is_right: Vec<Boolean<Fr>>,
+    siblings: Vec<FpVar<Fr>>,
+}
+
+impl AllocVar<PoseidonPath, Fr> for PoseidonPathVar {
+    fn new_variable<T: Borrow<PoseidonPath>>(
+        cs: impl Into<Namespace<Fr>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let ns = cs.into();
+        let cs = ns.cs();
+
+        f().and_then(|val| {
+            let path = val.borrow();
+            let position_bits: Vec<bool> = (0..path.siblings.len())
+                .map(|level| (path.leaf_index >> level) & 1 == 1)
+                .collect();
+
+            let is_right = Vec::new_variable(
+                ark_relations::ns!(cs, "path_bits"),
+                || Ok(&position_bits[..]),
+                mode,
+            )?;
+            let siblings = Vec::new_variable(
+                ark_relations::ns!(cs, "siblings"),
+                || Ok(&path.siblings[..]),
+                mode,
+            )?;
+
+            Ok(Self { is_right, siblings })
+        })
+    }
+}
+
+impl PoseidonPathVar {
+    /// Hashes `leaf` up along the path
+    pub fn compute_root(&self, leaf: &FpVar<Fr>) -> Result<FpVar<Fr>, SynthesisError> {
+        let params = UnitVar::default();
+        let mut curr = leaf.clone();
+        for (is_right, sibling) in self.is_right.iter().zip(&self.siblings) {
+            let left = FpVar::conditionally_select(is_right, sibling, &curr)?;
+            let right = FpVar::conditionally_select(is_right, &curr, sibling)?;
+            curr = <Bls12PoseidonCrh as TwoToOneCRHSchemeGadget<_, _>>::compress(
+                &params, &left, &right,
+            )?;
+        }
+
+        Ok(curr)
+    }
+
+    /// Returns whether `leaf` sits at this path's position in the tree with the given `root`
+    pub fn verify_membership(
+        &self,
+        root: &FpVar<Fr>,
+        leaf: &FpVar<Fr>,
+    ) -> Result<Boolean<Fr>, SynthesisError> {
+        self.compute_root(leaf)?.is_eq(root)
+    }
+}
+
+/// Proves that a private leaf is a member of the Merkle tree with a public root
+#[derive(Clone)]
+pub struct MembershipProof {
+    // Public inputs
+    pub root: Fr,
+
+    // Private witnesses
+    pub leaf: Fr,
+    pub path: PoseidonPath,
+}
+
+impl MembershipProof {
+    pub fn new(root: Fr, leaf: Fr, path: PoseidonPath) -> Self {
+        Self { root, leaf, path }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for MembershipProof {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
+        let root = FpVar::new_input(ark_relations::ns!(cs, "merkle_root"), || Ok(self.root))?;
+
+        let leaf = FpVar::new_witness(ark_relations::ns!(cs, "merkle_leaf"), || Ok(self.leaf))?;
+        let path =
+            PoseidonPathVar::new_witness(ark_relations::ns!(cs, "merkle_path"), || Ok(&self.path))?;
+
+        path.compute_root(&leaf)?.enforce_equal(&root)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bls12_381::Bls12_381;
+    use ark_ff::UniformRand;
+    use ark_groth16::Groth16;
+    use ark_r1cs_std::R1CSVar;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_snark::SNARK;
+
+    const HEIGHT: usize = 4;
+
+    fn random_tree() -> PoseidonMerkleTree {
+        let mut rng = rand::thread_rng();
+        let leaves: Vec<Fr> = (0..11).map(|_| Fr::rand(&mut rng)).collect();
+
+        PoseidonMerkleTree::new(&leaves, HEIGHT).unwrap()
+    }
+
+    #[test]
+    fn native_paths_verify() {
+        let tree = random_tree();
+
+        for index in 0..(1 << HEIGHT) {
+            let path = tree.generate_proof(index).unwrap();
+            let leaf = tree.leaf(index).unwrap();
+
+            assert!(path.verify(&tree.root(), &leaf).unwrap());
+            assert!(!path.verify(&tree.root(), &(leaf + Fr::from(1u8))).unwrap());
+        }
+    }
+
+    #[test]
+    fn too_many_leaves() {
+        let leaves = vec![Fr::zero(); 5];
+
+        assert!(PoseidonMerkleTree::new(&leaves, 2).is_err());
+        assert!(random_tree().generate_proof(1 << HEIGHT).is_err());
+    }
+
+    #[test]
+    fn gadget_matches_native() -> Result<()> {
+        let tree = random_tree();
+        let path = tree.generate_proof(6).unwrap();
+        let leaf = tree.leaf(6).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let leaf_var = FpVar::new_witness(cs.clone(), || Ok(leaf))?;
+        let path_var = PoseidonPathVar::new_witness(cs.clone(), || Ok(&path))?;
+
+        let root_var = FpVar::new_input(cs.clone(), || Ok(tree.root()))?;
+
+        assert_eq!(path_var.compute_root(&leaf_var)?.value()?, tree.root());
+        assert!(path_var.verify_membership(&root_var, &leaf_var)?.value()?);
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        let tree = random_tree();
+        let path = tree.generate_proof(3).unwrap();
+        let proof = MembershipProof::new(tree.root(), tree.leaf(3).unwrap(), path);
+
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a leaf at the wrong position does not satisfy the circuit
+    fn cs_wrong_leaf() -> Result<()> {
+        let tree = random_tree();
+        let path = tree.generate_proof(3).unwrap();
+        let proof = MembershipProof::new(tree.root(), tree.leaf(4).unwrap(), path);
+
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+
+        assert!(!cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a membership proof only verifies against the root it was made for
+    fn pf_membership() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let tree = random_tree();
+        let path = tree.generate_proof(9).unwrap();
+        let proof = MembershipProof::new(tree.root(), tree.leaf(9).unwrap(), path);
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(proof.clone(), &mut rng)?;
+        let pf = Groth16::<Bls12_381>::prove(&pk, proof, &mut rng)?;
+
+        assert!(Groth16::<Bls12_381>::verify(&vk, &[tree.root()], &pf)?);
+        assert!(!Groth16::<Bls12_381>::verify(
+            &vk,
+            &[random_tree().root()],
+            &pf
+        )?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use ark_bls12_381::Fr as BlsFr;
+use ark_crypto_primitives::Error as ArkError;
+use ark_ff::{Field, Zero};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    fields::{fp::FpVar, FieldVar},
+    R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use lazy_static::lazy_static;
+
+use crate::poseidon::{Bls12PoseidonCommitter, FieldHasher};
+use crate::round_constants::derive_constants;
+
+// x^3 isn't a permutation of the BLS12-381 scalar field, so we use x^5. This needs
+// 2 * ceil(log_5(p)) = 220 rounds.
+const MIMC_ROUNDS: usize = 220;
+
+lazy_static! {
+    static ref MIMC_ROUND_CONSTANTS: Vec<BlsFr> = derive_constants(b"mimc_feistel", MIMC_ROUNDS);
+}
+
+/// The MiMC-2n/n Feistel permutation with x^5 and a zero key, over BLS12-381
+pub fn mimc_feistel_permutation(mut left: BlsFr, mut right: BlsFr) -> (BlsFr, BlsFr) {
+    for c in MIMC_ROUND_CONSTANTS.iter() {
+        let t = left + c;
+        (left, right) = (right + t.square().square() * t, left);
+    }
+
+    (left, right)
+}
+
+// Computes right + (left + c)^5. The result is allocated and checked by the last multiplication
+// of the S-box, rather than left as a linear combination, since those would otherwise grow with
+// every round.
+fn round_gadget(
+    left: &FpVar<BlsFr>,
+    right: &FpVar<BlsFr>,
+    c: BlsFr,
+) -> Result<FpVar<BlsFr>, SynthesisError> {
+    let t = left + c;
+    if let (FpVar::Constant(t), FpVar::Constant(right)) = (&t, right) {
+        return Ok(FpVar::Constant(*right + t.square().square() * t));
+    }
+
+    let t4 = t.square()?.square()?;
+    let out = FpVar::new_witness(t.cs().or(right.cs()), || {
+        Ok(right.value()? + t4.value()? * t.value()?)
+    })?;
+    t4.mul_equals(&t, &(&out - right))?;
+
+    Ok(out)
+}
+
+/// The gadget version of [`mimc_feistel_permutation`]. This costs 3 constraints per round.
+pub fn mimc_feistel_permutation_gadget(
+    mut left: FpVar<BlsFr>,
+    mut right: FpVar<BlsFr>,
+) -> Result<(FpVar<BlsFr>, FpVar<BlsFr>), SynthesisError> {
+    for c in MIMC_ROUND_CONSTANTS.iter() {
+        (left, right) = (round_gadget(&left, &right, *c)?, left);
+    }
+
+    Ok((left, right))
+}
+
+/// A sponge over [`mimc_feistel_permutation`] with one element of rate and one of capacity. Every
+/// input element costs a permutation. The empty input is hashed as a single zero, as with
+/// [`crate::poseidon::Poseidon`].
+pub struct MimcFeistel;
+
+impl FieldHasher for MimcFeistel {
+    fn hash(input: &[BlsFr]) -> Result<BlsFr, ArkError> {
+        let (mut left, mut right) =
+            mimc_feistel_permutation(input.first().copied().unwrap_or_default(), BlsFr::zero());
+        for x in input.iter().skip(1) {
+            (left, right) = mimc_feistel_permutation(left + x, right);
+        }
+
+        Ok(left)
+    }
+
+    fn hash_gadget(
+        _cs: &ConstraintSystemRef<BlsFr>,
+        input: &[FpVar<BlsFr>],
+    ) -> Result<FpVar<BlsFr>, SynthesisError> {
+        let (mut left, mut right) = mimc_feistel_permutation_gadget(
+            input.first().cloned().unwrap_or_else(FpVar::zero),
+            FpVar::zero(),
+        )?;
+        for x in input.iter().skip(1) {
+            (left, right) = mimc_feistel_permutation_gadget(left + x, right)?;
+        }
+
+        Ok(left)
+    }
+}
+
+/// [`Bls12PoseidonCommitter`] with MiMC-Feistel in place of Poseidon
+pub type Bls12MimcCommitter = Bls12PoseidonCommitter<MimcFeistel>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::UnitVar;
+    use ark_crypto_primitives::commitment::{CommitmentGadget, CommitmentScheme};
+    use ark_ff::{MontFp, UniformRand};
+    use ark_r1cs_std::uint8::UInt8;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+
+    #[test]
+    /// Pin the round constants, which are generated rather than taken from a specification
+    fn hash_kat() {
+        let input = [1u8, 2, 3].map(BlsFr::from);
+
+        assert_eq!(
+            MimcFeistel::hash(&input).unwrap(),
+            MontFp!(
+                "34542807414258050280190674743132800549852155972583213928616864692577639113415"
+            )
+        );
+    }
+
+    #[test]
+    fn permutation_gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let (left, right) = (BlsFr::rand(&mut rng), BlsFr::rand(&mut rng));
+
+        let cs = ConstraintSystem::new_ref();
+        let left_var = FpVar::new_witness(cs.clone(), || Ok(left))?;
+        let right_var = FpVar::new_witness(cs.clone(), || Ok(right))?;
+        let (out_left, out_right) = mimc_feistel_permutation_gadget(left_var, right_var)?;
+
+        assert_eq!(
+            (out_left.value()?, out_right.value()?),
+            mimc_feistel_permutation(left, right)
+        );
+        assert!(cs.is_satisfied()?);
+        assert_eq!(cs.num_constraints(), 3 * MIMC_ROUNDS);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+
+        for n in [0, 1, 2, 5] {
+            let input: Vec<BlsFr> = (0..n).map(|_| BlsFr::rand(&mut rng)).collect();
+
+            let cs = ConstraintSystem::new_ref();
+            let input_var = Vec::new_witness(cs.clone(), || Ok(&input[..]))?;
+            let hash = MimcFeistel::hash_gadget(&cs, &input_var)?;
+
+            assert_eq!(hash.value()?, MimcFeistel::hash(&input).unwrap(), "n = {n}");
+            assert!(cs.is_satisfied()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn commit_gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let input = b"mimc commitment";
+        let r = BlsFr::rand(&mut rng);
+        let native = <Bls12MimcCommitter as CommitmentScheme>::commit(&(), input, &r).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let input_var = UInt8::new_witness_vec(cs.clone(), input)?;
+        let r_var = FpVar::new_witness(cs.clone(), || Ok(r))?;
+        let commitment = <Bls12MimcCommitter as CommitmentGadget<_, _>>::commit(
+            &UnitVar::default(),
+            &input_var,
+            &r_var,
+        )?;
+
+        assert_eq!(commitment.value()?, native);
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+use ark_bls12_381::Fr as BlsFr;
+use ark_crypto_primitives::{
+    commitment::{CommitmentGadget, CommitmentScheme},
+    Error as ArkError,
+};
+use ark_ec::{AffineRepr, CurveGroup, Group};
+use ark_ed_on_bls12_381::{
+    constraints::EdwardsVar, EdwardsAffine, EdwardsProjective, Fr as JubjubFr,
+};
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    boolean::Boolean,
+    groups::CurveVar,
+    uint8::UInt8,
+    ToBitsGadget,
+};
+use ark_relations::r1cs::{Namespace, SynthesisError};
+use ark_serialize::CanonicalDeserialize;
+use lazy_static::lazy_static;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Borrow,
+    sync::{RwLock, RwLockReadGuard},
+};
+
+use crate::poseidon::UnitVar;
+
+const GENERATOR_DOMAIN_SEP: &[u8] = b"jubjub_pedersen_generator";
+/// The length of a scalar in the byte input of [`Bls12PedersenCommitter`]
+pub const SCALAR_BYTES: usize = 32;
+
+// Hashes to a point in the prime-order subgroup by try-and-increment, so that nobody knows the
+// discrete logs between generators. Generator 0 is the blinding generator, and generator i + 1 is
+// the one for the i-th input.
+fn generator(index: u64) -> EdwardsProjective {
+    for ctr in 0u64.. {
+        let digest = Sha256::new()
+            .chain_update(GENERATOR_DOMAIN_SEP)
+            .chain_update(index.to_le_bytes())
+            .chain_update(ctr.to_le_bytes())
+            .finalize();
+        if let Some(point) = EdwardsAffine::from_random_bytes(&digest) {
+            let point = point.mul_by_cofactor_to_group();
+            if !point.is_zero() {
+                return point;
+            }
+        }
+    }
+    unreachable!("about half of all y-coordinates are on the curve")
+}
+
+// [g, 2g, 4g, ...], one per bit of a scalar, as precomputed_base_scalar_mul_le wants
+fn powers_of_two(g: EdwardsProjective) -> Vec<EdwardsProjective> {
+    std::iter::successors(Some(g), |p| Some(p.double()))
+        .take(JubjubFr::MODULUS_BIT_SIZE as usize)
+        .collect()
+}
+
+lazy_static! {
+    // The powers of two of every generator computed so far, indexed like `generator`. Inputs can
+    // be any length, so this grows as longer ones come in.
+    static ref GENERATORS: RwLock<Vec<Vec<EdwardsProjective>>> = RwLock::new(Vec::new());
+}
+
+// The powers of two of at least the first `count` generators. `generators(n)[i][0]` is generator i.
+fn generators(count: usize) -> RwLockReadGuard<'static, Vec<Vec<EdwardsProjective>>> {
+    if GENERATORS.read().unwrap().len() < count {
+        let mut cache = GENERATORS.write().unwrap();
+        while cache.len() < count {
+            let g = generator(cache.len() as u64);
+            cache.push(powers_of_two(g));
+        }
+    }
+
+    GENERATORS.read().unwrap()
+}
+
+fn decode_scalars(input: &[u8]) -> Result<Vec<JubjubFr>, ArkError> {
+    if !input.len().is_multiple_of(SCALAR_BYTES) {
+        return Err(format!(
+            "input of {} bytes is not a sequence of {SCALAR_BYTES}-byte scalars",
+            input.len()
+        )
+        .into());
+    }
+
+    input
+        .chunks(SCALAR_BYTES)
+        .map(|chunk| Ok(JubjubFr::deserialize_compressed(chunk)?))
+        .collect()
+}
+
+/// A Pedersen commitment over Jubjub, `r·H + Σ m_i·G_i`, to a vector of Jubjub scalars. Unlike
+/// [`crate::poseidon::Bls12PoseidonCommitter`] it is additively homomorphic: the sum of the
+/// commitments to `a` and `b` is the commitment to `a + b`, under the sum of the randomness.
+///
+/// The [`CommitmentScheme`] input is the concatenation of the inputs' canonical 32-byte
+/// little-endian encodings. Use [`Bls12PedersenCommitter::commit_scalars`] to skip the encoding.
+/// The generators are fixed, so there are no parameters.
+pub struct Bls12PedersenCommitter;
+
+impl Bls12PedersenCommitter {
+    /// Computes `r·H + Σ m_i·G_i`
+    pub fn commit_scalars(input: &[JubjubFr], r: &JubjubFr) -> EdwardsAffine {
+        let generators = generators(input.len() + 1);
+        let mut commitment = generators[0][0] * r;
+        for (i, m) in input.iter().enumerate() {
+            commitment += generators[i + 1][0] * m;
+        }
+
+        commitment.into_affine()
+    }
+
+    /// The gadget version of [`Bls12PedersenCommitter::commit_scalars`]. Each input costs about
+    /// 3 constraints per bit, and has to have exactly `MODULUS_BIT_SIZE` bits.
+    pub fn commit_scalars_gadget(
+        input: &[JubjubScalarVar],
+        r: &JubjubScalarVar,
+    ) -> Result<EdwardsVar, SynthesisError> {
+        let mut commitment = EdwardsVar::zero();
+        let generators = generators(input.len() + 1);
+        for (m, bases) in std::iter::once(r).chain(input).zip(generators.iter()) {
+            // Zipping with the bases would silently drop any extra bits
+            if m.0.len() != JubjubFr::MODULUS_BIT_SIZE as usize {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+            commitment.precomputed_base_scalar_mul_le(m.0.iter().zip(bases))?;
+        }
+
+        Ok(commitment)
+    }
+}
+
+impl CommitmentScheme for Bls12PedersenCommitter {
+    type Output = EdwardsAffine;
+    type Parameters = ();
+    type Randomness = JubjubFr;
+
+    fn setup<R: Rng>(_: &mut R) -> Result<Self::Parameters, ArkError> {
+        Ok(())
+    }
+
+    fn commit(
+        _parameters: &Self::Parameters,
+        input: &[u8],
+        r: &Self::Randomness,
+    ) -> Result<Self::Output, ArkError> {
+        Ok(Self::commit_scalars(&decode_scalars(input)?, r))
+    }
+}
+
+/// A Jubjub scalar in-circuit, as its little-endian bits. The bits aren't checked to be a
+/// canonical encoding, so a commitment opens to their value mod the group order.
+#[derive(Clone, Debug)]
+pub struct JubjubScalarVar(pub Vec<Boolean<BlsFr>>);
+
+impl AllocVar<JubjubFr, BlsFr> for JubjubScalarVar {
+    fn new_variable<T: Borrow<JubjubFr>>(
+        cs: impl Into<Namespace<BlsFr>>,
+        f: impl FnOnce() -> Result<T, SynthesisError>,
+        mode: AllocationMode,
+    ) -> Result<Self, SynthesisError> {
+        let cs = cs.into().cs();
+        let bits = f().map(|s| s.borrow().into_bigint().to_bits_le());
+
+        let bits = (0..JubjubFr::MODULUS_BIT_SIZE as usize)
+            .map(|i| {
+                Boolean::new_variable(
+                    cs.clone(),
+                    || bits.as_ref().map(|bits| bits[i]).map_err(|e| *e),
+                    mode,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self(bits))
+    }
+}
+
+impl CommitmentGadget<Bls12PedersenCommitter, BlsFr> for Bls12PedersenCommitter {
+    type OutputVar = EdwardsVar;
+    type ParametersVar = UnitVar<BlsFr>;
+    type RandomnessVar = JubjubScalarVar;
+
+    fn commit(
+        _parameters: &Self::ParametersVar,
+        input: &[UInt8<BlsFr>],
+        r: &Self::RandomnessVar,
+    ) -> Result<Self::OutputVar, SynthesisError> {
+        if !input.len().is_multiple_of(SCALAR_BYTES) {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        // Like `decode_scalars`, only accept canonical encodings: the top bits are zero, and the
+        // rest is at most r - 1
+        let max = (-JubjubFr::from(1u8)).into_bigint();
+        let scalars = input
+            .chunks(SCALAR_BYTES)
+            .map(|chunk| {
+                let mut bits = chunk.to_bits_le()?;
+                let _ = Boolean::enforce_smaller_or_equal_than_le(&bits, max)?;
+                bits.truncate(JubjubFr::MODULUS_BIT_SIZE as usize);
+                Ok(JubjubScalarVar(bits))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+        Self::commit_scalars_gadget(&scalars, r)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ff::UniformRand;
+    use ark_r1cs_std::{eq::EqGadget, R1CSVar};
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_serialize::CanonicalSerialize;
+
+    fn encode(scalars: &[JubjubFr]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for s in scalars {
+            s.serialize_compressed(&mut bytes).unwrap();
+        }
+        bytes
+    }
+
+    fn rand_scalars(n: usize) -> Vec<JubjubFr> {
+        let mut rng = rand::thread_rng();
+        (0..n).map(|_| JubjubFr::rand(&mut rng)).collect()
+    }
+
+    #[test]
+    /// Pin the generators, which are derived rather than taken from a specification
+    fn commit_kat() {
+        let input = [1u8, 2, 3].map(JubjubFr::from);
+        let commitment = Bls12PedersenCommitter::commit_scalars(&input, &JubjubFr::from(4u8));
+
+        let mut bytes = Vec::new();
+        commitment.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(
+            hex::encode(bytes),
+            "3fc20b68bebf4a8e97412bb1dd1d9a78fe5431b88da1896e1e7286ba0dfb7a6f"
+        );
+    }
+
+    #[test]
+    /// Check that the cache grows to fit longer inputs, and holds the same generators
+    fn cached_generators() {
+        let cached = generators(6).clone();
+
+        assert!(cached.len() >= 6);
+        for (i, bases) in cached.iter().enumerate().take(6) {
+            assert_eq!(*bases, powers_of_two(generator(i as u64)));
+        }
+    }
+
+    #[test]
+    fn bytes_match_scalars() {
+        let input = rand_scalars(3);
+        let r = JubjubFr::rand(&mut rand::thread_rng());
+
+        assert_eq!(
+            <Bls12PedersenCommitter as CommitmentScheme>::commit(&(), &encode(&input), &r).unwrap(),
+            Bls12PedersenCommitter::commit_scalars(&input, &r)
+        );
+    }
+
+    #[test]
+    fn bad_input() {
+        let r = JubjubFr::from(1u8);
+
+        // Not a whole number of scalars
+        assert!(<Bls12PedersenCommitter as CommitmentScheme>::commit(&(), &[0; 33], &r).is_err());
+        // Not a canonical encoding
+        assert!(
+            <Bls12PedersenCommitter as CommitmentScheme>::commit(&(), &[0xff; 32], &r).is_err()
+        );
+    }
+
+    #[test]
+    /// Check that Com(a, r) + Com(b, s) = Com(a + b, r + s)
+    fn homomorphic() {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (rand_scalars(3), rand_scalars(3));
+        let (r, s) = (JubjubFr::rand(&mut rng), JubjubFr::rand(&mut rng));
+        let sum: Vec<JubjubFr> = a.iter().zip(&b).map(|(a, b)| a + b).collect();
+
+        assert_eq!(
+            Bls12PedersenCommitter::commit_scalars(&a, &r)
+                + Bls12PedersenCommitter::commit_scalars(&b, &s),
+            Bls12PedersenCommitter::commit_scalars(&sum, &(r + s))
+        );
+    }
+
+    #[test]
+    fn gadget_matches_native() -> Result<()> {
+        let input = rand_scalars(2);
+        let r = JubjubFr::rand(&mut rand::thread_rng());
+        let native = Bls12PedersenCommitter::commit_scalars(&input, &r);
+
+        let cs = ConstraintSystem::new_ref();
+        let input_var = UInt8::new_witness_vec(cs.clone(), &encode(&input))?;
+        let r_var = JubjubScalarVar::new_witness(cs.clone(), || Ok(r))?;
+        let commitment = <Bls12PedersenCommitter as CommitmentGadget<_, _>>::commit(
+            &UnitVar::default(),
+            &input_var,
+            &r_var,
+        )?;
+
+        assert_eq!(commitment.value()?, native);
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that the circuit rejects the byte inputs the native decoder rejects, so `x` and
+    /// `x + 2^252`, or `x` and `x + r`, can't open the same commitment
+    fn non_canonical_bytes_in_circuit() -> Result<()> {
+        let r = JubjubFr::rand(&mut rand::thread_rng());
+        let x = encode(&rand_scalars(1));
+
+        let mut high_bit = x.clone();
+        high_bit[31] |= 0x10;
+        let mut modulus = Vec::new();
+        JubjubFr::MODULUS
+            .serialize_compressed(&mut modulus)
+            .unwrap();
+
+        let satisfied = |bytes: &[u8]| -> Result<bool> {
+            let cs = ConstraintSystem::new_ref();
+            let input_var = UInt8::new_witness_vec(cs.clone(), bytes)?;
+            let r_var = JubjubScalarVar::new_witness(cs.clone(), || Ok(r))?;
+            let _commitment = <Bls12PedersenCommitter as CommitmentGadget<_, _>>::commit(
+                &UnitVar::default(),
+                &input_var,
+                &r_var,
+            )?;
+            cs.is_satisfied()
+        };
+
+        assert!(satisfied(&x)?);
+        for bad in [high_bit, modulus, vec![0xff; 32]] {
+            assert!(<Bls12PedersenCommitter as CommitmentScheme>::commit(&(), &bad, &r).is_err());
+            assert!(!satisfied(&bad)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn short_scalar_var() -> Result<()> {
+        let cs = ConstraintSystem::<BlsFr>::new_ref();
+        let r = JubjubScalarVar::new_witness(cs.clone(), || Ok(JubjubFr::from(1u8)))?;
+        let short = JubjubScalarVar(r.0[..8].to_vec());
+
+        assert!(Bls12PedersenCommitter::commit_scalars_gadget(&[short], &r).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that Com(a, r) + Com(b, s) = Com(a + b, r + s) in-circuit, and that the check fails
+    /// for any other sum
+    fn homomorphic_in_circuit() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let (a, b) = (rand_scalars(2), rand_scalars(2));
+        let (r, s) = (JubjubFr::rand(&mut rng), JubjubFr::rand(&mut rng));
+        let com_a = Bls12PedersenCommitter::commit_scalars(&a, &r);
+        let com_b = Bls12PedersenCommitter::commit_scalars(&b, &s);
+
+        let check = |sum: Vec<JubjubFr>| -> Result<bool> {
+            let cs = ConstraintSystem::new_ref();
+            let com_a = EdwardsVar::new_input(cs.clone(), || Ok(com_a))?;
+            let com_b = EdwardsVar::new_input(cs.clone(), || Ok(com_b))?;
+            let sum_var = sum
+                .iter()
+                .map(|x| JubjubScalarVar::new_witness(cs.clone(), || Ok(x)))
+                .collect::<Result<Vec<_>>>()?;
+            let rnd_var = JubjubScalarVar::new_witness(cs.clone(), || Ok(r + s))?;
+
+            let com_sum = Bls12PedersenCommitter::commit_scalars_gadget(&sum_var, &rnd_var)?;
+            (com_a + com_b).enforce_equal(&com_sum)?;
+
+            cs.is_satisfied()
+        };
+
+        let sum: Vec<JubjubFr> = a.iter().zip(&b).map(|(a, b)| a + b).collect();
+        assert!(check(sum.clone())?);
+        assert!(!check(vec![sum[0], sum[1] + JubjubFr::from(1u8)])?);
+
+        Ok(())
+    }
+}
@@ -1,88 +1,225 @@
 use ark_bls12_381::Fr as BlsFr;
-use ark_crypto_primitives::{commitment::CommitmentGadget, Error as ArkError};
-use ark_ff::{
-    to_bytes,
-    ToConstraintField};
+use ark_crypto_primitives::{
+    commitment::{CommitmentGadget, CommitmentScheme},
+    crh::{CRHScheme, CRHSchemeGadget, TwoToOneCRHScheme, TwoToOneCRHSchemeGadget},
+    sponge::{
+        constraints::CryptographicSpongeVar,
+        poseidon::{
+            constraints::PoseidonSpongeVar, find_poseidon_ark_and_mds, PoseidonConfig,
+            PoseidonSponge,
+        },
+        CryptographicSponge, FieldBasedCryptographicSponge,
+    },
+    Error as ArkError,
+};
+use ark_ff::{BigInteger, PrimeField, ToConstraintField};
 use ark_r1cs_std::{
     alloc::{AllocVar, AllocationMode},
-    fields::fp::FpVar,
+    fields::{fp::FpVar, FieldVar},
     uint8::UInt8,
     R1CSVar, ToBytesGadget, ToConstraintFieldGadget,
 };
 use ark_relations::r1cs::{ConstraintSystemRef, Namespace, SynthesisError};
-use arkworks_native_gadgets::{
-    poseidon::{sbox::PoseidonSbox, FieldHasher, Poseidon, PoseidonParameters},
-    prelude::{ark_crypto_primitives::CommitmentScheme, ark_ff::PrimeField},
-};
-use arkworks_r1cs_gadgets::poseidon::{FieldHasherGadget, PoseidonGadget};
-use arkworks_utils::{bytes_matrix_to_f, bytes_vec_to_f, Curve};
+use arkworks_utils::Curve;
 use lazy_static::lazy_static;
 use rand::Rng;
 use std::{borrow::Borrow, marker::PhantomData};
 
 // from: https://github.com/rozbb/zkcreds-rs/blob/main/src/poseidon_utils.rs
 
-fn setup_poseidon_params<F: PrimeField>(curve: Curve, exp: i8, width: u8) -> PoseidonParameters<F> {
+// arkworks-utils only ships the raw parameter bytes, so we decode them ourselves
+fn setup_poseidon_params<F: PrimeField>(curve: Curve, exp: i8, width: u8) -> PoseidonConfig<F> {
     let pos_data =
         arkworks_utils::poseidon_params::setup_poseidon_params(curve, exp, width).unwrap();
 
-    let mds_f = bytes_matrix_to_f(&pos_data.mds);
-    let rounds_f = bytes_vec_to_f(&pos_data.rounds);
-
-    PoseidonParameters {
-        mds_matrix: mds_f,
-        round_keys: rounds_f,
-        full_rounds: pos_data.full_rounds,
-        partial_rounds: pos_data.partial_rounds,
-        sbox: PoseidonSbox(pos_data.exp),
-        width: pos_data.width,
-    }
+    let mds: Vec<Vec<F>> = pos_data
+        .mds
+        .iter()
+        .map(|row| row.iter().map(|x| F::from_be_bytes_mod_order(x)).collect())
+        .collect();
+    // The round constants are stored flattened, one `width`-sized chunk per round. There are more
+    // of them than there are rounds, and arkworks-native-gadgets only ever used the first ones
+    let num_rounds = (pos_data.full_rounds + pos_data.partial_rounds) as usize;
+    let ark: Vec<Vec<F>> = pos_data
+        .rounds
+        .chunks(pos_data.width as usize)
+        .take(num_rounds)
+        .map(|round| {
+            round
+                .iter()
+                .map(|x| F::from_be_bytes_mod_order(x))
+                .collect()
+        })
+        .collect();
+
+    // arkworks-native-gadgets hashed by permuting [0 || input] and returning the first element of
+    // the state. We get the same thing from a sponge with no capacity by absorbing the leading zero
+    // ourselves, see `poseidon_hash`.
+    PoseidonConfig::new(
+        pos_data.full_rounds as usize,
+        pos_data.partial_rounds as usize,
+        pos_data.exp as u64,
+        mds,
+        ark,
+        pos_data.width as usize,
+        0,
+    )
 }
 
 // Pick global parameters for Poseidon over BLS12-381
 const POSEIDON_WIDTH: u8 = 5;
 const COM_DOMAIN_SEP: &[u8] = b"pcom";
 const CRH_DOMAIN_SEP: &[u8] = b"pcrh";
+const X5_SPONGE_RATE: usize = 4;
+const X5_FULL_ROUNDS: usize = 8;
+const X5_PARTIAL_ROUNDS: usize = 60;
 lazy_static! {
-    static ref BLS12_POSEIDON_PARAMS: PoseidonParameters<BlsFr> =
+    pub(crate) static ref BLS12_POSEIDON_PARAMS: PoseidonConfig<BlsFr> =
         setup_poseidon_params(Curve::Bls381, 3, POSEIDON_WIDTH);
+
+    // BLS12_POSEIDON_PARAMS has no capacity, which is fine for hashing a fixed input but not for a
+    // sponge that's squeezed and then absorbed into again. This keeps the same permutation and
+    // sets aside one element of the state as capacity. The permutation's S-box is x^3, which isn't
+    // injective on the BLS12-381 scalar field, so this is only used for the transcript, which
+    // asked for these parameters. Anything new should use BLS12_POSEIDON_X5_SPONGE_PARAMS.
+    pub(crate) static ref BLS12_POSEIDON_SPONGE_PARAMS: PoseidonConfig<BlsFr> = {
+        let mut params = BLS12_POSEIDON_PARAMS.clone();
+        params.rate -= 1;
+        params.capacity = 1;
+        params
+    };
+
+    // A width-5 sponge with one element of capacity whose S-box is x^5, a permutation of the
+    // BLS12-381 scalar field since gcd(5, r - 1) = 1. The round counts are the Poseidon paper's for
+    // a 255-bit field, width 5 and x^5, and the constants come from its Grain LFSR.
+    pub(crate) static ref BLS12_POSEIDON_X5_SPONGE_PARAMS: PoseidonConfig<BlsFr> = {
+        let (ark, mds) = find_poseidon_ark_and_mds::<BlsFr>(
+            BlsFr::MODULUS_BIT_SIZE as u64,
+            X5_SPONGE_RATE,
+            X5_FULL_ROUNDS as u64,
+            X5_PARTIAL_ROUNDS as u64,
+            0,
+        );
+        PoseidonConfig::new(X5_FULL_ROUNDS, X5_PARTIAL_ROUNDS, 5, mds, ark, X5_SPONGE_RATE, 1)
+    };
+}
+
+/// The CRH only accepts 32-byte inputs, i.e., serialized BLS12-381 scalars
+const CRH_INPUT_LEN: usize = 32;
+
+/// Errors for malformed inputs to the Poseidon commitment and CRH
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoseidonInputError {
+    /// An input to the CRH was not `CRH_INPUT_LEN` bytes long
+    InvalidInputLength { expected: usize, got: usize },
+    /// The inputs could not be packed into field elements
+    PackingFailed,
+}
+
+impl core::fmt::Display for PoseidonInputError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PoseidonInputError::InvalidInputLength { expected, got } => {
+                write!(f, "expected an input of {expected} bytes, got {got}")
+            }
+            PoseidonInputError::PackingFailed => write!(f, "could not pack inputs"),
+        }
+    }
+}
+
+impl std::error::Error for PoseidonInputError {}
+
+fn check_crh_input_len(len: usize) -> Result<(), PoseidonInputError> {
+    if len != CRH_INPUT_LEN {
+        return Err(PoseidonInputError::InvalidInputLength {
+            expected: CRH_INPUT_LEN,
+            got: len,
+        });
+    }
+    Ok(())
+}
+
+/// A hash from any number of BLS12-381 scalars to one, together with its gadget. This is the
+/// part of [`Bls12PoseidonCommitter`] that can be switched out.
+pub trait FieldHasher {
+    fn hash(input: &[BlsFr]) -> Result<BlsFr, ArkError>;
+
+    fn hash_gadget(
+        cs: &ConstraintSystemRef<BlsFr>,
+        input: &[FpVar<BlsFr>],
+    ) -> Result<FpVar<BlsFr>, SynthesisError>;
+}
+
+/// The width-5 Poseidon hash, with the parameters in `BLS12_POSEIDON_PARAMS`. Inputs longer than
+/// 4 elements are hashed in blocks of 3, each chained with the hash of the blocks before it.
+pub struct Poseidon;
+
+impl FieldHasher for Poseidon {
+    fn hash(input: &[BlsFr]) -> Result<BlsFr, ArkError> {
+        poseidon_iterated_hash(input)
+    }
+
+    fn hash_gadget(
+        cs: &ConstraintSystemRef<BlsFr>,
+        input: &[FpVar<BlsFr>],
+    ) -> Result<FpVar<BlsFr>, SynthesisError> {
+        poseidon_iterated_hash_gadget(cs, input)
+    }
 }
 
-/// A commitment scheme defined using the Poseidon hash function over BLS12-381
-pub struct Bls12PoseidonCommitter;
+/// A commitment scheme defined using the Poseidon hash function over BLS12-381. The hash can be
+/// switched to [`crate::poseidon2::Poseidon2`], which gives different commitments.
+pub struct Bls12PoseidonCommitter<H: FieldHasher = Poseidon>(PhantomData<H>);
 
-fn poseidon_iterated_hash(input: &[BlsFr]) -> BlsFr {
-    let hasher = Poseidon::new(BLS12_POSEIDON_PARAMS.clone());
+// Hashes up to POSEIDON_WIDTH - 1 elements with a single permutation
+fn poseidon_hash(input: &[BlsFr]) -> BlsFr {
+    debug_assert!(input.len() < POSEIDON_WIDTH as usize);
+
+    let mut sponge = PoseidonSponge::new(&BLS12_POSEIDON_PARAMS);
+    sponge.absorb(&[&[BlsFr::from(0u8)], input].concat());
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+fn poseidon_hash_gadget(
+    cs: &ConstraintSystemRef<BlsFr>,
+    input: &[FpVar<BlsFr>],
+) -> Result<FpVar<BlsFr>, SynthesisError> {
+    debug_assert!(input.len() < POSEIDON_WIDTH as usize);
+
+    let mut sponge = PoseidonSpongeVar::new(cs.clone(), &BLS12_POSEIDON_PARAMS);
+    sponge.absorb(&[&[FpVar::zero()], input].concat())?;
+    Ok(sponge.squeeze_field_elements(1)?.remove(0))
+}
+
+pub(crate) fn poseidon_iterated_hash(input: &[BlsFr]) -> Result<BlsFr, ArkError> {
     let first_block_len = core::cmp::min(input.len(), (POSEIDON_WIDTH - 1) as usize);
 
     let first_block = &input[..first_block_len];
-    let mut running_hash = hasher.hash(first_block).unwrap();
+    let mut running_hash = poseidon_hash(first_block);
     for block in input[first_block_len..].chunks((POSEIDON_WIDTH - 2) as usize) {
         let next_input = &[&[running_hash], block].concat();
-        running_hash = hasher.hash(next_input).unwrap();
+        running_hash = poseidon_hash(next_input);
     }
-    running_hash
+    Ok(running_hash)
 }
 
-fn poseidon_iterated_hash_gadget(
-    cs: &mut ConstraintSystemRef<BlsFr>,
+pub(crate) fn poseidon_iterated_hash_gadget(
+    cs: &ConstraintSystemRef<BlsFr>,
     input: &[FpVar<BlsFr>],
 ) -> Result<FpVar<BlsFr>, SynthesisError> {
-    let hasher = Poseidon::new(BLS12_POSEIDON_PARAMS.clone());
-    let hasher_var = PoseidonGadget::from_native(cs, hasher)?;
     let first_block_len = core::cmp::min(input.len(), (POSEIDON_WIDTH - 1) as usize);
 
     let first_block = &input[..first_block_len];
-    let mut running_hash = hasher_var.hash(first_block)?;
+    let mut running_hash = poseidon_hash_gadget(cs, first_block)?;
     for block in input[first_block_len..].chunks((POSEIDON_WIDTH - 2) as usize) {
         let next_input = &[&[running_hash], block].concat();
-        running_hash = hasher_var.hash(next_input)?;
+        running_hash = poseidon_hash_gadget(cs, next_input)?;
     }
 
     Ok(running_hash)
 }
 
-impl CommitmentScheme for Bls12PoseidonCommitter {
+impl<H: FieldHasher> CommitmentScheme for Bls12PoseidonCommitter<H> {
     type Output = BlsFr;
     // We don't need parameters because they're set globally in the above lazy_static
     type Parameters = ();
@@ -99,13 +236,13 @@ impl CommitmentScheme for Bls12PoseidonCommitter {
         r: &Self::Randomness,
     ) -> Result<Self::Output, ArkError> {
         // Concat all the inputs and pack them into field elements
-        let hash_input: Vec<u8> = [COM_DOMAIN_SEP, &to_bytes!(r).unwrap(), input].concat();
+        let hash_input: Vec<u8> = [COM_DOMAIN_SEP, &r.into_bigint().to_bytes_le(), input].concat();
         let packed_input: Vec<BlsFr> = hash_input
             .to_field_elements()
-            .expect("could not pack inputs");
+            .ok_or(PoseidonInputError::PackingFailed)?;
 
         // Compute the hash
-        Ok(poseidon_iterated_hash(&packed_input))
+        H::hash(&packed_input)
     }
 }
 
@@ -124,7 +261,9 @@ impl<ConstraintF: PrimeField> AllocVar<(), ConstraintF> for UnitVar<ConstraintF>
     }
 }
 
-impl CommitmentGadget<Bls12PoseidonCommitter, BlsFr> for Bls12PoseidonCommitter {
+impl<H: FieldHasher> CommitmentGadget<Bls12PoseidonCommitter<H>, BlsFr>
+    for Bls12PoseidonCommitter<H>
+{
     type OutputVar = FpVar<BlsFr>;
     type ParametersVar = UnitVar<BlsFr>;
     type RandomnessVar = FpVar<BlsFr>;
@@ -135,65 +274,104 @@ impl CommitmentGadget<Bls12PoseidonCommitter, BlsFr> for Bls12PoseidonCommitter
         input: &[UInt8<BlsFr>],
         r: &Self::RandomnessVar,
     ) -> Result<Self::OutputVar, SynthesisError> {
-        let mut cs = input.cs();
+        let cs = input.cs();
 
         // Concat all the inputs and pack them into field elements
-        let hash_input: Vec<UInt8<BlsFr>> = [
-            &UInt8::constant_vec(COM_DOMAIN_SEP),
-            &r.to_bytes().unwrap(),
-            input,
-        ]
-        .concat();
-        let packed_input: Vec<FpVar<BlsFr>> = hash_input
-            .to_constraint_field()
-            .expect("could not pack inputs");
+        let hash_input: Vec<UInt8<BlsFr>> =
+            [&UInt8::constant_vec(COM_DOMAIN_SEP), &r.to_bytes()?, input].concat();
+        let packed_input: Vec<FpVar<BlsFr>> = hash_input.to_constraint_field()?;
 
         // Compute the hash
-        poseidon_iterated_hash_gadget(&mut cs, &packed_input)
+        H::hash_gadget(&cs, &packed_input)
     }
 }
 
 /// Represents the collision-resistant hashing functionality of Poseidon over BLS12-381
 pub struct Bls12PoseidonCrh;
 
-// TODO: Once arkworks-native-gadgets updates to the new Arkworks version, update this to use the
-// new Arkworks trait TwoToOneCRHScheme
-// https://github.com/webb-tools/arkworks-gadgets/blob/master/arkworks-native-gadgets/src/mimc.rs#L2=
-use ark_crypto_primitives::crh::{TwoToOneCRH, TwoToOneCRHGadget};
-
-impl TwoToOneCRH for Bls12PoseidonCrh {
-    // This doesn't matter. We only use it for Merkle tree stuff
-    const LEFT_INPUT_SIZE_BITS: usize = 0;
-    const RIGHT_INPUT_SIZE_BITS: usize = 0;
+impl Bls12PoseidonCrh {
+    // The domain separator, packed into a single field element
+    fn domain_sep() -> BlsFr {
+        BlsFr::from_le_bytes_mod_order(CRH_DOMAIN_SEP)
+    }
+}
 
+impl CRHScheme for Bls12PoseidonCrh {
+    type Input = [BlsFr];
+    type Output = BlsFr;
     type Parameters = ();
+
+    fn setup<R: Rng>(_: &mut R) -> Result<Self::Parameters, ArkError> {
+        Ok(())
+    }
+
+    // Evaluates H(domain_sep || input) directly on field elements
+    fn evaluate<T: Borrow<[BlsFr]>>(_: &(), input: T) -> Result<BlsFr, ArkError> {
+        poseidon_iterated_hash(&[&[Self::domain_sep()], input.borrow()].concat())
+    }
+}
+
+impl TwoToOneCRHScheme for Bls12PoseidonCrh {
+    type Input = [u8];
     type Output = BlsFr;
+    type Parameters = ();
 
     fn setup<R: Rng>(_: &mut R) -> Result<Self::Parameters, ArkError> {
         Ok(())
     }
 
     // Evaluates H(left || right)
-    fn evaluate(_: &(), left_input: &[u8], right_input: &[u8]) -> Result<BlsFr, ArkError> {
+    fn evaluate<T: Borrow<[u8]>>(_: &(), left_input: T, right_input: T) -> Result<BlsFr, ArkError> {
+        let (left_input, right_input) = (left_input.borrow(), right_input.borrow());
+
         // We only use this for Merkle tree hashing over BLS12-381, so just fix the input len to 32
-        assert_eq!(left_input.len(), 32);
-        assert_eq!(right_input.len(), 32);
+        check_crh_input_len(left_input.len())?;
+        check_crh_input_len(right_input.len())?;
 
         // Concat all the inputs and pack them into field elements
         let hash_input: Vec<u8> = [CRH_DOMAIN_SEP, left_input, right_input].concat();
         let packed_input: Vec<BlsFr> = hash_input
             .to_field_elements()
-            .expect("could not pack inputs");
+            .ok_or(PoseidonInputError::PackingFailed)?;
 
         // Compute the hash
-        Ok(poseidon_iterated_hash(&packed_input))
+        poseidon_iterated_hash(&packed_input)
+    }
+
+    // Evaluates H(domain_sep || left || right) directly on field elements. This is what the
+    // Poseidon Merkle tree uses for its inner nodes. It costs a single Poseidon permutation, but
+    // note that it does NOT agree with `evaluate` on the byte encodings of `left` and `right`.
+    fn compress<T: Borrow<BlsFr>>(
+        _: &(),
+        left_input: T,
+        right_input: T,
+    ) -> Result<BlsFr, ArkError> {
+        <Self as CRHScheme>::evaluate(&(), [*left_input.borrow(), *right_input.borrow()])
     }
 }
 
 // Do the same thing for ZK land
-impl TwoToOneCRHGadget<Bls12PoseidonCrh, BlsFr> for Bls12PoseidonCrh {
+impl CRHSchemeGadget<Bls12PoseidonCrh, BlsFr> for Bls12PoseidonCrh {
+    type InputVar = [FpVar<BlsFr>];
+    type OutputVar = FpVar<BlsFr>;
     type ParametersVar = UnitVar<BlsFr>;
+
+    // Evaluates H(domain_sep || input) directly on field elements
+    fn evaluate(
+        _: &UnitVar<BlsFr>,
+        input: &[FpVar<BlsFr>],
+    ) -> Result<FpVar<BlsFr>, SynthesisError> {
+        let cs = input.cs();
+        let domain_sep = FpVar::constant(Self::domain_sep());
+
+        poseidon_iterated_hash_gadget(&cs, &[&[domain_sep], input].concat())
+    }
+}
+
+impl TwoToOneCRHSchemeGadget<Bls12PoseidonCrh, BlsFr> for Bls12PoseidonCrh {
+    type InputVar = [UInt8<BlsFr>];
     type OutputVar = FpVar<BlsFr>;
+    type ParametersVar = UnitVar<BlsFr>;
 
     // Evaluates H(left || right)
     fn evaluate(
@@ -201,11 +379,15 @@ impl TwoToOneCRHGadget<Bls12PoseidonCrh, BlsFr> for Bls12PoseidonCrh {
         left_input: &[UInt8<BlsFr>],
         right_input: &[UInt8<BlsFr>],
     ) -> Result<FpVar<BlsFr>, SynthesisError> {
-        // We only use this for Merkle tree hashing over BLS12-381, so just fix the input len to 32
-        assert_eq!(left_input.len(), 32);
-        assert_eq!(right_input.len(), 32);
+        // We only use this for Merkle tree hashing over BLS12-381, so just fix the input len to 32.
+        // There's no SynthesisError for bad inputs, so report them as unsatisfiable
+        if check_crh_input_len(left_input.len()).is_err()
+            || check_crh_input_len(right_input.len()).is_err()
+        {
+            return Err(SynthesisError::Unsatisfiable);
+        }
 
-        let mut cs = left_input.cs().or(right_input.cs());
+        let cs = left_input.cs().or(right_input.cs());
 
         // Concat all the inputs and pack them into field elements
         let hash_input: Vec<UInt8<_>> = [
@@ -214,11 +396,225 @@ impl TwoToOneCRHGadget<Bls12PoseidonCrh, BlsFr> for Bls12PoseidonCrh {
             right_input,
         ]
         .concat();
-        let packed_input: Vec<FpVar<BlsFr>> = hash_input
-            .to_constraint_field()
-            .expect("could not pack inputs");
+        let packed_input: Vec<FpVar<BlsFr>> = hash_input.to_constraint_field()?;
 
         // Compute the hash
-        poseidon_iterated_hash_gadget(&mut cs, &packed_input)
+        poseidon_iterated_hash_gadget(&cs, &packed_input)
+    }
+
+    // Evaluates H(domain_sep || left || right) directly on field elements
+    fn compress(
+        params: &UnitVar<BlsFr>,
+        left_input: &FpVar<BlsFr>,
+        right_input: &FpVar<BlsFr>,
+    ) -> Result<FpVar<BlsFr>, SynthesisError> {
+        <Self as CRHSchemeGadget<_, _>>::evaluate(
+            params,
+            &[left_input.clone(), right_input.clone()],
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ff::{MontFp, UniformRand};
+    use ark_relations::r1cs::ConstraintSystem;
+    use serde::Deserialize;
+    use std::str::FromStr;
+
+    fn crh_input_var(cs: ConstraintSystemRef<BlsFr>, len: usize) -> Vec<UInt8<BlsFr>> {
+        UInt8::new_witness_vec(cs, &vec![7u8; len]).unwrap()
+    }
+
+    fn expect_length_error(err: ArkError, got: usize) {
+        assert_eq!(
+            err.downcast_ref::<PoseidonInputError>(),
+            Some(&PoseidonInputError::InvalidInputLength {
+                expected: CRH_INPUT_LEN,
+                got
+            })
+        );
+    }
+
+    #[derive(Deserialize)]
+    struct TestVectors {
+        iterated_hash: Vec<HashVector>,
+        commit: Vec<CommitVector>,
+    }
+
+    #[derive(Deserialize)]
+    struct HashVector {
+        description: String,
+        input: Vec<String>,
+        output: String,
+    }
+
+    #[derive(Deserialize)]
+    struct CommitVector {
+        description: String,
+        input: String,
+        randomness: String,
+        output: String,
+    }
+
+    fn parse_fr(s: &str) -> BlsFr {
+        BlsFr::from_str(s).unwrap_or_else(|_| panic!("invalid field element {s}"))
+    }
+
+    // Kept in a JSON file so that other implementations can check themselves against it
+    fn test_vectors() -> TestVectors {
+        serde_json::from_str(include_str!("../test_vectors/poseidon.json")).unwrap()
+    }
+
+    #[test]
+    fn iterated_hash_kat() {
+        for v in test_vectors().iterated_hash {
+            let input: Vec<BlsFr> = v.input.iter().map(|s| parse_fr(s)).collect();
+            let expected = parse_fr(&v.output);
+
+            assert_eq!(
+                poseidon_iterated_hash(&input).unwrap(),
+                expected,
+                "{}",
+                v.description
+            );
+
+            let cs = ConstraintSystem::new_ref();
+            let input_var = Vec::new_witness(cs.clone(), || Ok(&input[..])).unwrap();
+            let hash = poseidon_iterated_hash_gadget(&cs, &input_var).unwrap();
+
+            assert_eq!(hash.value().unwrap(), expected, "{}", v.description);
+            assert!(cs.is_satisfied().unwrap(), "{}", v.description);
+        }
+    }
+
+    #[test]
+    fn commit_kat() {
+        for v in test_vectors().commit {
+            let input = hex::decode(&v.input).unwrap();
+            let r = parse_fr(&v.randomness);
+            let expected = parse_fr(&v.output);
+
+            assert_eq!(
+                <Bls12PoseidonCommitter as CommitmentScheme>::commit(&(), &input, &r).unwrap(),
+                expected,
+                "{}",
+                v.description
+            );
+
+            let cs = ConstraintSystem::new_ref();
+            let input_var = UInt8::new_witness_vec(cs.clone(), &input).unwrap();
+            let r_var = FpVar::new_witness(cs.clone(), || Ok(r)).unwrap();
+            let commitment = <Bls12PoseidonCommitter as CommitmentGadget<_, _>>::commit(
+                &UnitVar::default(),
+                &input_var,
+                &r_var,
+            )
+            .unwrap();
+
+            assert_eq!(commitment.value().unwrap(), expected, "{}", v.description);
+            assert!(cs.is_satisfied().unwrap(), "{}", v.description);
+        }
+    }
+
+    #[test]
+    fn crh_kat() {
+        let evaluate =
+            <Bls12PoseidonCrh as TwoToOneCRHScheme>::evaluate(&(), &[1u8; 32][..], &[2u8; 32][..]);
+        let compress = <Bls12PoseidonCrh as TwoToOneCRHScheme>::compress(
+            &(),
+            BlsFr::from(1u8),
+            BlsFr::from(2u8),
+        );
+
+        assert_eq!(
+            evaluate.unwrap(),
+            MontFp!("66648222481736906110702822040910379101638872812250062984987527496846526236")
+        );
+        assert_eq!(
+            compress.unwrap(),
+            MontFp!(
+                "24273705008610022320280665025872619322669781291634550156258080180573865833598"
+            )
+        );
+    }
+
+    #[test]
+    fn crh_gadget_matches_native() {
+        let mut rng = rand::thread_rng();
+        let input: Vec<BlsFr> = (0..5).map(|_| BlsFr::rand(&mut rng)).collect();
+        let native = <Bls12PoseidonCrh as CRHScheme>::evaluate(&(), &input[..]).unwrap();
+        let compressed =
+            <Bls12PoseidonCrh as TwoToOneCRHScheme>::compress(&(), input[0], input[1]).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let params = UnitVar::default();
+        let input_var = Vec::new_witness(cs.clone(), || Ok(&input[..])).unwrap();
+        let gadget =
+            <Bls12PoseidonCrh as CRHSchemeGadget<_, _>>::evaluate(&params, &input_var).unwrap();
+        let compressed_var = <Bls12PoseidonCrh as TwoToOneCRHSchemeGadget<_, _>>::compress(
+            &params,
+            &input_var[0],
+            &input_var[1],
+        )
+        .unwrap();
+
+        assert_eq!(gadget.value().unwrap(), native);
+        assert_eq!(compressed_var.value().unwrap(), compressed);
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn crh_accepts_scalars() {
+        let mut rng = rand::thread_rng();
+        let left = BlsFr::rand(&mut rng).into_bigint().to_bytes_le();
+        let right = BlsFr::rand(&mut rng).into_bigint().to_bytes_le();
+
+        let native =
+            <Bls12PoseidonCrh as TwoToOneCRHScheme>::evaluate(&(), &left[..], &right[..]).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let left_var = UInt8::new_witness_vec(cs.clone(), &left).unwrap();
+        let right_var = UInt8::new_witness_vec(cs.clone(), &right).unwrap();
+        let gadget = <Bls12PoseidonCrh as TwoToOneCRHSchemeGadget<_, _>>::evaluate(
+            &UnitVar::default(),
+            &left_var,
+            &right_var,
+        )
+        .unwrap();
+
+        assert_eq!(gadget.value().unwrap(), native);
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn crh_rejects_bad_left_input() {
+        let err =
+            <Bls12PoseidonCrh as TwoToOneCRHScheme>::evaluate(&(), &[0u8; 31][..], &[0u8; 32][..]);
+        expect_length_error(err.unwrap_err(), 31);
+    }
+
+    #[test]
+    fn crh_rejects_bad_right_input() {
+        let err =
+            <Bls12PoseidonCrh as TwoToOneCRHScheme>::evaluate(&(), &[0u8; 32][..], &[0u8; 100][..]);
+        expect_length_error(err.unwrap_err(), 100);
+    }
+
+    #[test]
+    fn crh_gadget_rejects_bad_inputs() {
+        let cs = ConstraintSystem::new_ref();
+        let params = UnitVar::default();
+
+        for (left_len, right_len) in [(0, 32), (32, 33), (64, 64)] {
+            let left = crh_input_var(cs.clone(), left_len);
+            let right = crh_input_var(cs.clone(), right_len);
+            let res = <Bls12PoseidonCrh as TwoToOneCRHSchemeGadget<_, _>>::evaluate(
+                &params, &left, &right,
+            );
+
+            assert!(matches!(res, Err(SynthesisError::Unsatisfiable)));
+        }
     }
 }
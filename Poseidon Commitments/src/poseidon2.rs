@@ -0,0 +1,280 @@
+use ark_bls12_381::Fr as BlsFr;
+use ark_crypto_primitives::Error as ArkError;
+use ark_ff::{Field, PrimeField, Zero};
+use ark_r1cs_std::fields::{fp::FpVar, FieldVar};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use lazy_static::lazy_static;
+use std::{
+    convert::Infallible,
+    ops::{Add, Mul},
+};
+
+use crate::poseidon::FieldHasher;
+use crate::poseidon2_constants::{
+    EXTERNAL_ROUND_CONSTANTS, INTERNAL_DIAG_M_1, INTERNAL_ROUND_CONSTANTS,
+};
+
+// Poseidon2 isn't defined for width 5, so we use the closest standard instance. Every hash block
+// holds one element less than with `Poseidon`.
+pub const POSEIDON2_WIDTH: usize = 4;
+
+struct Poseidon2Params {
+    /// One row per full round, the first half before the partial rounds and the rest after
+    external_rc: Vec<[BlsFr; POSEIDON2_WIDTH]>,
+    /// One constant per partial round
+    internal_rc: Vec<BlsFr>,
+    /// The internal matrix is `1 + diag(internal_diag_m_1)`
+    internal_diag_m_1: [BlsFr; POSEIDON2_WIDTH],
+}
+
+fn parse_constant(hex_str: &str) -> BlsFr {
+    let bytes = hex::decode(hex_str.trim_start_matches("0x")).expect("invalid Poseidon2 constant");
+    BlsFr::from_be_bytes_mod_order(&bytes)
+}
+
+lazy_static! {
+    static ref BLS12_POSEIDON2_PARAMS: Poseidon2Params = Poseidon2Params {
+        external_rc: EXTERNAL_ROUND_CONSTANTS
+            .iter()
+            .map(|round| round.map(parse_constant))
+            .collect(),
+        internal_rc: INTERNAL_ROUND_CONSTANTS.map(parse_constant).to_vec(),
+        internal_diag_m_1: INTERNAL_DIAG_M_1.map(parse_constant),
+    };
+}
+
+// The linear layers only add and multiply by constants, so they're shared between the native and
+// the gadget versions. In R1CS they cost no constraints at all.
+
+// Multiplies by the 4x4 MDS matrix from the Poseidon2 paper, using the addition chain from the
+// reference implementation
+fn external_linear_layer<T>(state: [T; POSEIDON2_WIDTH]) -> [T; POSEIDON2_WIDTH]
+where
+    T: Clone + Add<Output = T>,
+{
+    let double = |x: &T| x.clone() + x.clone();
+    let [x0, x1, x2, x3] = state;
+
+    let t0 = x0 + x1.clone();
+    let t1 = x2 + x3.clone();
+    let t2 = double(&x1) + t1.clone();
+    let t3 = double(&x3) + t0.clone();
+    let t4 = double(&double(&t1)) + t3.clone();
+    let t5 = double(&double(&t0)) + t2.clone();
+    let t6 = t3 + t5.clone();
+    let t7 = t2 + t4.clone();
+
+    [t6, t5, t7, t4]
+}
+
+// Multiplies by 1 + diag(internal_diag_m_1)
+fn internal_linear_layer<T>(state: [T; POSEIDON2_WIDTH]) -> [T; POSEIDON2_WIDTH]
+where
+    T: Clone + Add<Output = T> + Mul<BlsFr, Output = T>,
+{
+    let [x0, x1, x2, x3] = &state;
+    let sum = x0.clone() + x1.clone() + x2.clone() + x3.clone();
+
+    let mut i = 0;
+    state.map(|x| {
+        let y = x * BLS12_POSEIDON2_PARAMS.internal_diag_m_1[i] + sum.clone();
+        i += 1;
+        y
+    })
+}
+
+// The Poseidon2 permutation, generic over the state type so that the native and gadget versions
+// only differ in how they compute the S-box x^5
+fn permute<T, E>(
+    state: [T; POSEIDON2_WIDTH],
+    sbox: impl Fn(T) -> Result<T, E>,
+) -> Result<[T; POSEIDON2_WIDTH], E>
+where
+    T: Clone + Add<Output = T> + Add<BlsFr, Output = T> + Mul<BlsFr, Output = T>,
+{
+    let params = &*BLS12_POSEIDON2_PARAMS;
+    let (first_rc, last_rc) = params.external_rc.split_at(params.external_rc.len() / 2);
+
+    let full_round = |state: [T; POSEIDON2_WIDTH], rc: &[BlsFr; POSEIDON2_WIDTH]| {
+        let mut i = 0;
+        let state = state.map(|x| {
+            let y = x + rc[i];
+            i += 1;
+            y
+        });
+        let [x0, x1, x2, x3] = state;
+        Ok(external_linear_layer([
+            sbox(x0)?,
+            sbox(x1)?,
+            sbox(x2)?,
+            sbox(x3)?,
+        ]))
+    };
+
+    let mut state = external_linear_layer(state);
+    for rc in first_rc {
+        state = full_round(state, rc)?;
+    }
+    for &rc in &params.internal_rc {
+        let [x0, x1, x2, x3] = state;
+        state = internal_linear_layer([sbox(x0 + rc)?, x1, x2, x3]);
+    }
+    for rc in last_rc {
+        state = full_round(state, rc)?;
+    }
+
+    Ok(state)
+}
+
+/// The Poseidon2 permutation over BLS12-381 with width 4 and x^5 S-boxes
+pub fn poseidon2_permutation(state: [BlsFr; POSEIDON2_WIDTH]) -> [BlsFr; POSEIDON2_WIDTH] {
+    let sbox = |x: BlsFr| Ok::<_, Infallible>(x.square().square() * x);
+    permute(state, sbox).unwrap_or_else(|e| match e {})
+}
+
+/// The gadget version of [`poseidon2_permutation`]. This costs 3 constraints per S-box.
+pub fn poseidon2_permutation_gadget(
+    state: [FpVar<BlsFr>; POSEIDON2_WIDTH],
+) -> Result<[FpVar<BlsFr>; POSEIDON2_WIDTH], SynthesisError> {
+    let sbox = |x: FpVar<BlsFr>| Ok(x.square()?.square()? * x);
+    permute(state, sbox)
+}
+
+// Hashes up to POSEIDON2_WIDTH - 1 elements with a single permutation, the same way `Poseidon`
+// does: permute [0 || input], zero padded, and keep the first element
+fn poseidon2_hash(input: &[BlsFr]) -> BlsFr {
+    debug_assert!(input.len() < POSEIDON2_WIDTH);
+
+    let mut state = [BlsFr::zero(); POSEIDON2_WIDTH];
+    state[1..=input.len()].copy_from_slice(input);
+    poseidon2_permutation(state)[0]
+}
+
+fn poseidon2_hash_gadget(input: &[FpVar<BlsFr>]) -> Result<FpVar<BlsFr>, SynthesisError> {
+    debug_assert!(input.len() < POSEIDON2_WIDTH);
+
+    let mut state = [(); POSEIDON2_WIDTH].map(|_| FpVar::zero());
+    state[1..=input.len()].clone_from_slice(input);
+    Ok(poseidon2_permutation_gadget(state)?[0].clone())
+}
+
+/// Hashes with the Poseidon2 permutation, chaining blocks in the same way as
+/// [`crate::poseidon::Poseidon`]
+pub struct Poseidon2;
+
+impl FieldHasher for Poseidon2 {
+    fn hash(input: &[BlsFr]) -> Result<BlsFr, ArkError> {
+        let first_block_len = core::cmp::min(input.len(), POSEIDON2_WIDTH - 1);
+
+        let mut running_hash = poseidon2_hash(&input[..first_block_len]);
+        for block in input[first_block_len..].chunks(POSEIDON2_WIDTH - 2) {
+            running_hash = poseidon2_hash(&[&[running_hash], block].concat());
+        }
+
+        Ok(running_hash)
+    }
+
+    fn hash_gadget(
+        _cs: &ConstraintSystemRef<BlsFr>,
+        input: &[FpVar<BlsFr>],
+    ) -> Result<FpVar<BlsFr>, SynthesisError> {
+        let first_block_len = core::cmp::min(input.len(), POSEIDON2_WIDTH - 1);
+
+        let mut running_hash = poseidon2_hash_gadget(&input[..first_block_len])?;
+        for block in input[first_block_len..].chunks(POSEIDON2_WIDTH - 2) {
+            running_hash = poseidon2_hash_gadget(&[&[running_hash], block].concat())?;
+        }
+
+        Ok(running_hash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::{Bls12PoseidonCommitter, Poseidon, UnitVar};
+    use ark_crypto_primitives::commitment::{CommitmentGadget, CommitmentScheme};
+    use ark_ff::UniformRand;
+    use ark_r1cs_std::{alloc::AllocVar, uint8::UInt8, R1CSVar};
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+
+    #[test]
+    fn permutation_kat() {
+        // From the reference implementation's tests
+        let input = [0u8, 1, 2, 3].map(BlsFr::from);
+        let expected = [
+            "0x28ff6c4edf9768c08ae26290487e93449cc8bc155fc2fad92a344adceb3ada6d",
+            "0x0e56f2b6fad25075aa93560185b70e2b180ed7e269159c507c288b6747a0db2d",
+            "0x6d8196f28da6006bb89b3df94600acdc03d0ba7c2b0f3f4409a54c1db6bf30d0",
+            "0x07cfb49540ee456cce38b8a7d1a930a57ffc6660737f6589ef184c5e15334e36",
+        ]
+        .map(parse_constant);
+
+        assert_eq!(poseidon2_permutation(input), expected);
+    }
+
+    #[test]
+    fn permutation_gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let state = [(); POSEIDON2_WIDTH].map(|_| BlsFr::rand(&mut rng));
+
+        let cs = ConstraintSystem::new_ref();
+        let state_var = Vec::new_witness(cs.clone(), || Ok(&state[..]))?;
+        let out = poseidon2_permutation_gadget(state_var.try_into().unwrap())?;
+
+        assert_eq!(out.value()?, poseidon2_permutation(state));
+        assert!(cs.is_satisfied()?);
+        // Only the S-boxes cost anything: 8 full rounds of 4 and 56 partial rounds of 1
+        assert_eq!(cs.num_constraints(), 3 * (8 * 4 + 56));
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+
+        // Empty, within the first block, on block boundaries and past them
+        for n in [0, 1, 3, 4, 5, 6, 10] {
+            let input: Vec<BlsFr> = (0..n).map(|_| BlsFr::rand(&mut rng)).collect();
+
+            let cs = ConstraintSystem::new_ref();
+            let input_var = Vec::new_witness(cs.clone(), || Ok(&input[..]))?;
+            let hash = Poseidon2::hash_gadget(&cs, &input_var)?;
+
+            assert_eq!(hash.value()?, Poseidon2::hash(&input).unwrap(), "n = {n}");
+            assert!(cs.is_satisfied()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that the committer can be switched to Poseidon2, and that this changes the commitment
+    fn commit_with_poseidon2() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let input = b"switching permutations";
+        let r = BlsFr::rand(&mut rng);
+
+        let native =
+            <Bls12PoseidonCommitter<Poseidon2> as CommitmentScheme>::commit(&(), input, &r)
+                .unwrap();
+        let poseidon =
+            <Bls12PoseidonCommitter<Poseidon> as CommitmentScheme>::commit(&(), input, &r).unwrap();
+        assert_ne!(native, poseidon);
+
+        let cs = ConstraintSystem::new_ref();
+        let input_var = UInt8::new_witness_vec(cs.clone(), input)?;
+        let r_var = FpVar::new_witness(cs.clone(), || Ok(r))?;
+        let commitment = <Bls12PoseidonCommitter<Poseidon2> as CommitmentGadget<_, _>>::commit(
+            &UnitVar::default(),
+            &input_var,
+            &r_var,
+        )?;
+
+        assert_eq!(commitment.value()?, native);
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use ark_bls12_381::Fr as BlsFr;
+use ark_ff::PrimeField;
+use sha2::{Digest, Sha256};
+
+/// Derives `n` nothing-up-my-sleeve constants as SHA-256(label || i), reduced mod the BLS12-381
+/// scalar field. MiMC and Rescue have no standard instances over this field, so their round
+/// constants come from here.
+pub(crate) fn derive_constants(label: &[u8], n: usize) -> Vec<BlsFr> {
+    (0..n as u64)
+        .map(|i| {
+            let digest = Sha256::new()
+                .chain_update(label)
+                .chain_update(i.to_le_bytes())
+                .finalize();
+            BlsFr::from_le_bytes_mod_order(&digest)
+        })
+        .collect()
+}
@@ -0,0 +1,152 @@
+use ark_bls12_381::Fr;
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+
+use crate::commit_gadget;
+
+/// Proves that a public commitment, made with [`crate::commit`], opens to one of a public set of
+/// values, without revealing which.
+///
+/// The public inputs are the commitment followed by the set. See
+/// [`SetMembershipProof::public_inputs`]. The value is checked to be a root of
+/// `Π (x - s_i)`, which costs one constraint per element, and the circuit shape depends on the
+/// size of the set, so each size needs its own keys. Nothing is in the empty set, so synthesizing
+/// with one fails with [`SynthesisError::Unsatisfiable`].
+#[derive(Clone)]
+pub struct SetMembershipProof {
+    // Public inputs
+    pub commitment: Fr,
+    pub set: Vec<Fr>,
+
+    // Private witnesses
+    pub value: Fr,
+    pub com_rnd: Fr,
+}
+
+impl SetMembershipProof {
+    pub fn new(value: Fr, com_rnd: Fr, commitment: Fr, set: Vec<Fr>) -> Self {
+        Self {
+            commitment,
+            set,
+            value,
+            com_rnd,
+        }
+    }
+
+    /// The public inputs a verifier needs to check a proof of this statement
+    pub fn public_inputs(&self) -> Vec<Fr> {
+        [vec![self.commitment], self.set.clone()].concat()
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for SetMembershipProof {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
+        let public_commitment = FpVar::new_input(ark_relations::ns!(cs, "pub commitment"), || {
+            Ok(self.commitment)
+        })?;
+        let set = Vec::<FpVar<Fr>>::new_input(ark_relations::ns!(cs, "set"), || Ok(&self.set[..]))?;
+
+        let r = FpVar::new_witness(ark_relations::ns!(cs, "com_rnd"), || Ok(self.com_rnd))?;
+        let value = FpVar::new_witness(ark_relations::ns!(cs, "value"), || Ok(self.value))?;
+
+        commit_gadget(&value, &r)?.enforce_equal(&public_commitment)?;
+
+        // The product over the empty set is the constant 1, and r1cs-std doesn't enforce
+        // anything when comparing constants
+        if set.is_empty() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut product = FpVar::one();
+        for s in &set {
+            product *= &value - s;
+        }
+        product.enforce_equal(&FpVar::zero())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::create_new_commitment;
+    use ark_bls12_381::Bls12_381;
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_snark::SNARK;
+
+    fn set() -> Vec<Fr> {
+        [2u8, 3, 5, 7, 11].map(Fr::from).to_vec()
+    }
+
+    fn create_new_proof(value: u8) -> SetMembershipProof {
+        let opening = create_new_commitment(value.into(), &mut rand::thread_rng()).unwrap();
+
+        SetMembershipProof::new(opening.value, opening.cmd_rnd, opening.commitment, set())
+    }
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        for value in [2, 7, 11] {
+            let cs = ConstraintSystem::new_ref();
+            create_new_proof(value).generate_constraints(cs.clone())?;
+
+            assert!(cs.is_satisfied()?, "value = {value}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a value outside the set does not satisfy the circuit
+    fn cs_not_in_set() -> Result<()> {
+        for value in [0, 4, 12] {
+            let cs = ConstraintSystem::new_ref();
+            create_new_proof(value).generate_constraints(cs.clone())?;
+
+            assert!(!cs.is_satisfied()?, "value = {value}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that nothing is in the empty set
+    fn cs_empty_set() {
+        let mut proof = create_new_proof(2);
+        proof.set.clear();
+
+        let cs = ConstraintSystem::new_ref();
+
+        assert!(matches!(
+            proof.generate_constraints(cs),
+            Err(SynthesisError::Unsatisfiable)
+        ));
+    }
+
+    #[test]
+    /// Check that a true proof is valid, and doesn't verify against a different set
+    fn pf_complete_and_sound() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let proof = create_new_proof(5);
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(proof.clone(), &mut rng)?;
+        let pf = Groth16::<Bls12_381>::prove(&pk, proof.clone(), &mut rng)?;
+
+        assert!(Groth16::<Bls12_381>::verify(
+            &vk,
+            &proof.public_inputs(),
+            &pf
+        )?);
+
+        let mut public_inputs = proof.public_inputs();
+        public_inputs[3] = Fr::from(6u8);
+        assert!(!Groth16::<Bls12_381>::verify(&vk, &public_inputs, &pf)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use ark_bls12_381::Fr as BlsFr;
+use ark_crypto_primitives::sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonSponge},
+    CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_ed_on_bls12_381::{constraints::EdwardsVar, EdwardsAffine};
+use ark_ff::ToConstraintField;
+use ark_r1cs_std::fields::{fp::FpVar, FieldVar};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+
+use crate::poseidon::BLS12_POSEIDON_SPONGE_PARAMS;
+
+// Labels are absorbed along with their length, so that a label can't run into the data after it
+fn label_elements(label: &[u8]) -> Vec<BlsFr> {
+    let packed: Vec<BlsFr> = label
+        .to_field_elements()
+        .expect("bytes always pack into field elements");
+    [vec![BlsFr::from(label.len() as u64)], packed].concat()
+}
+
+fn label_vars(label: &[u8]) -> Vec<FpVar<BlsFr>> {
+    label_elements(label)
+        .into_iter()
+        .map(FpVar::constant)
+        .collect()
+}
+
+/// A Fiat–Shamir transcript over the BLS12-381 scalar field, built on a Poseidon sponge. Every
+/// message and challenge carries a label, and [`TranscriptVar`] replays the same transcript
+/// in-circuit.
+#[derive(Clone)]
+pub struct Transcript {
+    sponge: PoseidonSponge<BlsFr>,
+}
+
+impl Transcript {
+    /// Starts a transcript for the protocol named `label`
+    pub fn new(label: &[u8]) -> Self {
+        let mut sponge = PoseidonSponge::new(&BLS12_POSEIDON_SPONGE_PARAMS);
+        sponge.absorb(&label_elements(label));
+
+        Self { sponge }
+    }
+
+    pub fn append_scalar(&mut self, label: &[u8], scalar: &BlsFr) {
+        self.append_scalars(label, &[*scalar]);
+    }
+
+    /// Appends a list of scalars. The length is appended too, so lists can be told apart from
+    /// whatever comes after them.
+    pub fn append_scalars(&mut self, label: &[u8], scalars: &[BlsFr]) {
+        self.sponge.absorb(&label_elements(label));
+        self.sponge.absorb(&BlsFr::from(scalars.len() as u64));
+        self.sponge.absorb(&scalars);
+    }
+
+    /// Appends a Jubjub point, whose coordinates live in the BLS12-381 scalar field
+    pub fn append_point(&mut self, label: &[u8], point: &EdwardsAffine) {
+        self.append_scalars(label, &[point.x, point.y]);
+    }
+
+    /// Squeezes out a challenge that depends on everything appended so far
+    pub fn challenge_scalar(&mut self, label: &[u8]) -> BlsFr {
+        self.sponge.absorb(&label_elements(label));
+        self.sponge.squeeze_native_field_elements(1)[0]
+    }
+}
+
+/// The gadget version of [`Transcript`]. The same sequence of calls, with the same labels, gives
+/// the same challenges.
+#[derive(Clone)]
+pub struct TranscriptVar {
+    sponge: PoseidonSpongeVar<BlsFr>,
+}
+
+impl TranscriptVar {
+    /// Starts a transcript for the protocol named `label`
+    pub fn new(cs: ConstraintSystemRef<BlsFr>, label: &[u8]) -> Result<Self, SynthesisError> {
+        let mut sponge = PoseidonSpongeVar::new(cs, &BLS12_POSEIDON_SPONGE_PARAMS);
+        sponge.absorb(&label_vars(label))?;
+
+        Ok(Self { sponge })
+    }
+
+    pub fn append_scalar(
+        &mut self,
+        label: &[u8],
+        scalar: &FpVar<BlsFr>,
+    ) -> Result<(), SynthesisError> {
+        self.append_scalars(label, std::slice::from_ref(scalar))
+    }
+
+    pub fn append_scalars(
+        &mut self,
+        label: &[u8],
+        scalars: &[FpVar<BlsFr>],
+    ) -> Result<(), SynthesisError> {
+        self.sponge.absorb(&label_vars(label))?;
+        self.sponge
+            .absorb(&FpVar::constant(BlsFr::from(scalars.len() as u64)))?;
+        self.sponge.absorb(&scalars.to_vec())
+    }
+
+    pub fn append_point(&mut self, label: &[u8], point: &EdwardsVar) -> Result<(), SynthesisError> {
+        self.append_scalars(label, &[point.x.clone(), point.y.clone()])
+    }
+
+    pub fn challenge_scalar(&mut self, label: &[u8]) -> Result<FpVar<BlsFr>, SynthesisError> {
+        self.sponge.absorb(&label_vars(label))?;
+        Ok(self.sponge.squeeze_field_elements(1)?.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ed_on_bls12_381::Fr as JubjubFr;
+    use ark_ff::{BigInteger, PrimeField, UniformRand};
+    use ark_r1cs_std::{
+        alloc::AllocVar, boolean::Boolean, eq::EqGadget, groups::CurveVar, R1CSVar, ToBitsGadget,
+    };
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+
+    #[test]
+    /// Check that challenges depend on the labels, the data and the order of appends
+    fn challenges_bind_transcript() {
+        let (a, b) = (BlsFr::from(1u8), BlsFr::from(2u8));
+        let challenge = |labels: [&[u8]; 2], scalars: [BlsFr; 2]| {
+            let mut transcript = Transcript::new(b"test");
+            transcript.append_scalar(labels[0], &scalars[0]);
+            transcript.append_scalar(labels[1], &scalars[1]);
+            transcript.challenge_scalar(b"c")
+        };
+
+        let expected = challenge([b"a", b"b"], [a, b]);
+        assert_eq!(challenge([b"a", b"b"], [a, b]), expected);
+        assert_ne!(challenge([b"a", b"x"], [a, b]), expected);
+        assert_ne!(challenge([b"a", b"b"], [a, a]), expected);
+        assert_ne!(challenge([b"a", b"b"], [b, a]), expected);
+
+        // Successive challenges differ
+        let mut transcript = Transcript::new(b"test");
+        let c1 = transcript.challenge_scalar(b"c");
+        let c2 = transcript.challenge_scalar(b"c");
+        assert_ne!(c1, c2);
+    }
+
+    #[test]
+    fn gadget_matches_native() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let scalars: Vec<BlsFr> = (0..6).map(|_| BlsFr::rand(&mut rng)).collect();
+        let point = EdwardsAffine::rand(&mut rng);
+
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_scalar(b"x", &scalars[0]);
+        transcript.append_point(b"P", &point);
+        let c1 = transcript.challenge_scalar(b"c1");
+        transcript.append_scalars(b"xs", &scalars[1..]);
+        let c2 = transcript.challenge_scalar(b"c2");
+        let c3 = transcript.challenge_scalar(b"c3");
+
+        let cs = ConstraintSystem::new_ref();
+        let scalar_vars = Vec::new_witness(cs.clone(), || Ok(&scalars[..]))?;
+        let point_var = EdwardsVar::new_witness(cs.clone(), || Ok(point))?;
+
+        let mut transcript_var = TranscriptVar::new(cs.clone(), b"test")?;
+        transcript_var.append_scalar(b"x", &scalar_vars[0])?;
+        transcript_var.append_point(b"P", &point_var)?;
+        let c1_var = transcript_var.challenge_scalar(b"c1")?;
+        transcript_var.append_scalars(b"xs", &scalar_vars[1..])?;
+        let c2_var = transcript_var.challenge_scalar(b"c2")?;
+        let c3_var = transcript_var.challenge_scalar(b"c3")?;
+
+        assert_eq!(
+            [c1_var.value()?, c2_var.value()?, c3_var.value()?],
+            [c1, c2, c3]
+        );
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a Schnorr proof of knowledge of a Jubjub discrete log, made non-interactive with
+    /// the native transcript, verifies in-circuit
+    fn sigma_protocol_in_circuit() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let g = EdwardsAffine::generator();
+        let sk = JubjubFr::rand(&mut rng);
+        let pk = (g * sk).into_affine();
+
+        // Prove
+        let k = JubjubFr::rand(&mut rng);
+        let r = (g * k).into_affine();
+        let mut transcript = Transcript::new(b"schnorr pok");
+        transcript.append_point(b"pk", &pk);
+        transcript.append_point(b"R", &r);
+        let c = transcript.challenge_scalar(b"c");
+        let c = JubjubFr::from_le_bytes_mod_order(&c.into_bigint().to_bytes_le());
+        let s = k + c * sk;
+
+        // Verify in-circuit: s·G == R + c·pk
+        let cs = ConstraintSystem::new_ref();
+        let pk_var = EdwardsVar::new_input(cs.clone(), || Ok(pk))?;
+        let r_var = EdwardsVar::new_witness(cs.clone(), || Ok(r))?;
+        let s_bits =
+            Vec::<Boolean<_>>::new_witness(cs.clone(), || Ok(s.into_bigint().to_bits_le()))?;
+
+        let mut transcript_var = TranscriptVar::new(cs.clone(), b"schnorr pok")?;
+        transcript_var.append_point(b"pk", &pk_var)?;
+        transcript_var.append_point(b"R", &r_var)?;
+        let c_var = transcript_var.challenge_scalar(b"c")?;
+
+        let lhs = EdwardsVar::constant(g.into()).scalar_mul_le(s_bits.iter())?;
+        let rhs = r_var + pk_var.scalar_mul_le(c_var.to_bits_le()?.iter())?;
+        lhs.enforce_equal(&rhs)?;
+
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+}
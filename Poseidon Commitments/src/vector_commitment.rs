@@ -0,0 +1,261 @@
+use ark_bls12_381::Fr;
+use ark_crypto_primitives::Error as ArkError;
+use ark_ff::PrimeField;
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use std::collections::BTreeSet;
+
+use crate::poseidon::{poseidon_iterated_hash, poseidon_iterated_hash_gadget};
+
+const VEC_COM_DOMAIN_SEP: &[u8] = b"pvec";
+
+/// A commitment to a vector of attributes, defined using the Poseidon hash function over
+/// BLS12-381. Unlike [`crate::poseidon::Bls12PoseidonCommitter`], this works on field elements
+/// directly, so each attribute costs a third of a Poseidon permutation in-circuit.
+pub struct Bls12PoseidonVectorCommitter;
+
+impl Bls12PoseidonVectorCommitter {
+    // The domain separator, packed into a single field element
+    fn domain_sep() -> Fr {
+        Fr::from_le_bytes_mod_order(VEC_COM_DOMAIN_SEP)
+    }
+
+    /// Computes H(domain_sep || len || randomness || attrs). The length is hashed in so that
+    /// vectors of different lengths can never share a commitment.
+    pub fn commit(attrs: &[Fr], r: &Fr) -> Result<Fr, ArkError> {
+        let hash_input = [
+            &[Self::domain_sep(), Fr::from(attrs.len() as u64), *r],
+            attrs,
+        ]
+        .concat();
+
+        poseidon_iterated_hash(&hash_input)
+    }
+
+    /// The gadget version of [`Bls12PoseidonVectorCommitter::commit`]
+    pub fn commit_gadget(attrs: &[FpVar<Fr>], r: &FpVar<Fr>) -> Result<FpVar<Fr>, SynthesisError> {
+        let cs = attrs.cs().or(r.cs());
+        let header = [
+            FpVar::constant(Self::domain_sep()),
+            FpVar::constant(Fr::from(attrs.len() as u64)),
+            r.clone(),
+        ];
+        let hash_input = [&header[..], attrs].concat();
+
+        poseidon_iterated_hash_gadget(&cs, &hash_input)
+    }
+}
+
+/// Proves knowledge of the full opening of a public vector commitment, while revealing the
+/// attributes at a chosen set of positions.
+///
+/// The public inputs are the commitment followed by the revealed attributes, in increasing order
+/// of position. See [`SelectiveOpeningProof::public_inputs`]. The circuit shape depends on the
+/// number of attributes and on which positions are revealed, so each choice needs its own keys.
+#[derive(Clone)]
+pub struct SelectiveOpeningProof {
+    // Public inputs
+    pub commitment: Fr,
+    pub revealed: BTreeSet<usize>,
+
+    // Private witnesses
+    pub attrs: Vec<Fr>,
+    pub com_rnd: Fr,
+}
+
+impl SelectiveOpeningProof {
+    pub fn new(
+        attrs: Vec<Fr>,
+        com_rnd: Fr,
+        commitment: Fr,
+        revealed: impl IntoIterator<Item = usize>,
+    ) -> Result<Self, ArkError> {
+        let revealed: BTreeSet<usize> = revealed.into_iter().collect();
+        if let Some(&pos) = revealed.iter().find(|&&pos| pos >= attrs.len()) {
+            return Err(format!(
+                "cannot reveal position {pos} of a vector of {} attributes",
+                attrs.len()
+            )
+            .into());
+        }
+
+        Ok(Self {
+            commitment,
+            revealed,
+            attrs,
+            com_rnd,
+        })
+    }
+
+    /// The revealed attributes, in increasing order of position
+    pub fn revealed_attrs(&self) -> Vec<Fr> {
+        self.revealed.iter().map(|&pos| self.attrs[pos]).collect()
+    }
+
+    /// The public inputs a verifier needs to check a proof of this statement
+    pub fn public_inputs(&self) -> Vec<Fr> {
+        [vec![self.commitment], self.revealed_attrs()].concat()
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for SelectiveOpeningProof {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> ark_relations::r1cs::Result<()> {
+        let public_commitment = FpVar::new_input(ark_relations::ns!(cs, "pub commitment"), || {
+            Ok(self.commitment)
+        })?;
+
+        // Allocate the revealed attributes first, so that they come right after the commitment
+        // in the public inputs
+        let mut attrs: Vec<Option<FpVar<Fr>>> = vec![None; self.attrs.len()];
+        for &pos in &self.revealed {
+            attrs[pos] = Some(FpVar::new_input(
+                ark_relations::ns!(cs, "revealed attr"),
+                || Ok(self.attrs[pos]),
+            )?);
+        }
+        let attrs = attrs
+            .into_iter()
+            .zip(&self.attrs)
+            .map(|(var, attr)| match var {
+                Some(var) => Ok(var),
+                None => FpVar::new_witness(ark_relations::ns!(cs, "hidden attr"), || Ok(attr)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let r = FpVar::new_witness(ark_relations::ns!(cs, "com_rnd"), || Ok(self.com_rnd))?;
+
+        let commitment = Bls12PoseidonVectorCommitter::commit_gadget(&attrs, &r)?;
+        commitment.enforce_equal(&public_commitment)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bls12_381::Bls12_381;
+    use ark_ff::{MontFp, UniformRand};
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::{ConstraintSystem, Result};
+    use ark_snark::SNARK;
+
+    const NUM_ATTRS: usize = 6;
+
+    fn create_new_opening(revealed: &[usize]) -> SelectiveOpeningProof {
+        let mut rng = rand::thread_rng();
+        let attrs: Vec<Fr> = (0..NUM_ATTRS).map(|_| Fr::rand(&mut rng)).collect();
+        let rnd = Fr::rand(&mut rng);
+        let commitment = Bls12PoseidonVectorCommitter::commit(&attrs, &rnd).unwrap();
+
+        SelectiveOpeningProof::new(attrs, rnd, commitment, revealed.iter().copied()).unwrap()
+    }
+
+    #[test]
+    fn gadget_matches_native() -> Result<()> {
+        let opening = create_new_opening(&[]);
+
+        let cs = ConstraintSystem::new_ref();
+        let attrs = Vec::new_witness(cs.clone(), || Ok(&opening.attrs[..]))?;
+        let r = FpVar::new_witness(cs.clone(), || Ok(opening.com_rnd))?;
+        let commitment = Bls12PoseidonVectorCommitter::commit_gadget(&attrs, &r)?;
+
+        assert_eq!(commitment.value()?, opening.commitment);
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn commit_kat() {
+        let attrs = [Fr::from(1u8), Fr::from(2u8), Fr::from(3u8)];
+        let commitment = Bls12PoseidonVectorCommitter::commit(&attrs, &Fr::from(4u8)).unwrap();
+
+        assert_eq!(
+            commitment,
+            MontFp!(
+                "24030046029276014674873881341019165213553384989955569626128415265169630407799"
+            )
+        );
+    }
+
+    #[test]
+    /// Check that the vector length is bound by the commitment
+    fn length_is_committed() {
+        let rnd = Fr::from(5u8);
+        let attrs = [Fr::from(1u8), Fr::from(2u8)];
+
+        let short = Bls12PoseidonVectorCommitter::commit(&attrs, &rnd).unwrap();
+        let padded =
+            Bls12PoseidonVectorCommitter::commit(&[&attrs[..], &[Fr::from(0u8)]].concat(), &rnd)
+                .unwrap();
+
+        assert_ne!(short, padded);
+    }
+
+    #[test]
+    fn bad_position() {
+        let opening = create_new_opening(&[]);
+        let res = SelectiveOpeningProof::new(
+            opening.attrs,
+            opening.com_rnd,
+            opening.commitment,
+            [1, NUM_ATTRS],
+        );
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        let opening = create_new_opening(&[0, 3, 5]);
+
+        let cs = ConstraintSystem::new_ref();
+        opening.clone().generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+        assert_eq!(cs.num_instance_variables(), 1 + 1 + 3);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a true proof is valid
+    fn pf_complete() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let opening = create_new_opening(&[1, 4]);
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(opening.clone(), &mut rng)?;
+        let proof = Groth16::<Bls12_381>::prove(&pk, opening.clone(), &mut rng)?;
+
+        assert!(Groth16::<Bls12_381>::verify(
+            &vk,
+            &opening.public_inputs(),
+            &proof
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that a proof does not verify against a different revealed attribute
+    fn pf_sound() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let opening = create_new_opening(&[1, 4]);
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(opening.clone(), &mut rng)?;
+        let proof = Groth16::<Bls12_381>::prove(&pk, opening.clone(), &mut rng)?;
+
+        let mut public_inputs = opening.public_inputs();
+        public_inputs[2] += Fr::from(1u8);
+
+        assert!(!Groth16::<Bls12_381>::verify(&vk, &public_inputs, &proof)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+//! Batch verification against verifying the same signatures one at a time. "batch" starts from
+//! signatures made with `sign_batchable`, and "batch_from_plain" from plain `Signature`s, which
+//! first have to have their commitments recomputed.
+
+use ark_ec::models::twisted_edwards::Projective as TEProjective;
+use ark_ed_on_bls12_381::JubjubConfig;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use schnorr::{batch::BatchableSignature, SchnorrSystem};
+
+type Jubjub = TEProjective<JubjubConfig>;
+
+fn verification(c: &mut Criterion) {
+    let system = SchnorrSystem::<Jubjub>::from_domain(b"schnorr benches");
+    let mut rng = rand::thread_rng();
+    let mut group = c.benchmark_group("verification");
+
+    for n in [16, 64, 256] {
+        let signed: Vec<_> = (0..n)
+            .map(|i| {
+                let user = system.new_user(&mut rng);
+                let msg = format!("message {i}").into_bytes();
+                let sig: BatchableSignature<Jubjub> = user.sign_batchable(&msg).unwrap();
+                (user.pk, msg, sig)
+            })
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("sequential", n), &signed, |b, signed| {
+            b.iter(|| {
+                signed
+                    .iter()
+                    .all(|(pk, msg, sig)| system.verify(pk, msg, &sig.signature()).unwrap())
+            })
+        });
+
+        let mut batch = system.batch_verifier();
+        for (pk, msg, sig) in &signed {
+            batch.queue(pk, msg, sig);
+        }
+        group.bench_with_input(BenchmarkId::new("batch", n), &batch, |b, batch| {
+            b.iter(|| batch.verify(&mut rand::thread_rng()).unwrap())
+        });
+
+        let plain: Vec<_> = signed
+            .iter()
+            .map(|(pk, msg, sig)| (*pk, msg.clone(), sig.signature()))
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::new("batch_from_plain", n),
+            &plain,
+            |b, plain| {
+                b.iter(|| {
+                    let mut batch = system.batch_verifier();
+                    for (pk, msg, sig) in plain {
+                        let sig = BatchableSignature::new(system.params(), pk, sig);
+                        batch.queue(pk, msg, &sig);
+                    }
+                    batch.verify(&mut rand::thread_rng()).unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, verification);
+criterion_main!(benches);
@@ -0,0 +1,306 @@
+//! Batch verification of signatures by [`User`]s of a [`SchnorrSystem`]. It only saves time on
+//! signatures made with [`User::sign_batchable`]. A plain `Signature` from [`User::sign`] has no
+//! commitment, and [`BatchableSignature::new`] costs as much as verifying it, so batching
+//! existing signatures is no faster than checking them one at a time.
+//!
+//! An arkworks `Signature` is a response `s` and a challenge `e`, and checking it means
+//! recomputing the commitment `R = s·G + e·pk` and hashing it, two scalar multiplications per
+//! signature. A [`BatchableSignature`] carries `R` as well, computed once by the signer. Then
+//! each challenge is checked against `R` by hashing alone, and the equations `s·G + e·pk - R = 0`
+//! are checked together as a random linear combination
+//!
+//! `(Σ zᵢ·sᵢ)·G + Σ (zᵢ·eᵢ)·pkᵢ - Σ zᵢ·Rᵢ = 0`
+//!
+//! with one multi-scalar multiplication. The `zᵢ` are random 128-bit scalars, so a batch with an
+//! invalid signature passes with probability at most 2^-128. When it fails, the batch is bisected
+//! with the same `zᵢ` to find an invalid signature.
+//!
+//! This matches checking each signature on its own as long as the `Rᵢ` and public keys are in the
+//! prime-order subgroup. They are when they come from signing or from validated deserialization,
+//! which is arkworks' default. A key holder could otherwise build an `R` with a small-order
+//! component that fails on its own but passes a batch with small probability.
+
+use ark_crypto_primitives::{
+    crh::sha256::Sha256,
+    signature::schnorr::{Parameters, PublicKey, Signature},
+    Error,
+};
+use ark_ec::CurveGroup;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::Rng;
+use std::ops::Range;
+
+use crate::{schnorr_challenge, SchnorrSystem, User};
+
+/// A signature that verifies under [`SchnorrSystem::verify`] as [`Self::signature`], along with
+/// its commitment `R = s·G + e·pk`
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct BatchableSignature<C: CurveGroup> {
+    pub commitment: C::Affine,
+    pub prover_response: C::ScalarField,
+    pub verifier_challenge: C::ScalarField,
+}
+
+impl<C: CurveGroup> BatchableSignature<C> {
+    /// Attaches the commitment to an existing signature by `pk`. This costs as much as verifying
+    /// the signature, so signers should use [`User::sign_batchable`] instead.
+    pub fn new(params: &Parameters<C, Sha256>, pk: &PublicKey<C>, sig: &Signature<C>) -> Self {
+        let commitment =
+            (params.generator * sig.prover_response + *pk * sig.verifier_challenge).into_affine();
+
+        Self {
+            commitment,
+            prover_response: sig.prover_response,
+            verifier_challenge: sig.verifier_challenge,
+        }
+    }
+
+    /// The signature without its commitment
+    pub fn signature(&self) -> Signature<C> {
+        Signature {
+            prover_response: self.prover_response,
+            verifier_challenge: self.verifier_challenge,
+        }
+    }
+}
+
+impl<C: CurveGroup> User<C> {
+    /// Signs `msg` like [`User::sign`], keeping the commitment so the signature can be verified
+    /// in a batch
+    pub fn sign_batchable(&self, msg: &[u8]) -> Result<BatchableSignature<C>, Error> {
+        let sig = self.sign(msg)?;
+        Ok(BatchableSignature::new(&self.params, &self.pk, &sig))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    /// The signature at this position in the batch is invalid
+    InvalidSignature(usize),
+}
+
+impl core::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BatchError::InvalidSignature(i) => write!(f, "signature {i} in the batch is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// Signatures queued to be verified together. Make one with [`SchnorrSystem::batch_verifier`].
+#[derive(Clone, Debug)]
+pub struct BatchVerifier<C: CurveGroup> {
+    params: Parameters<C, Sha256>,
+    items: Vec<(PublicKey<C>, Vec<u8>, BatchableSignature<C>)>,
+}
+
+impl<C: CurveGroup> BatchVerifier<C> {
+    /// Adds a signature on `msg` by the holder of `pk` to the batch. Signatures are numbered in
+    /// the order they're queued, from 0.
+    pub fn queue(&mut self, pk: &PublicKey<C>, msg: &[u8], sig: &BatchableSignature<C>) {
+        self.items.push((*pk, msg.to_vec(), sig.clone()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Checks every queued signature, and if one is invalid, returns its position. An empty
+    /// batch is valid.
+    pub fn verify<R: Rng>(&self, rng: &mut R) -> Result<(), BatchError> {
+        // The challenges only take a hash each, so a bad one is found without any group operations
+        for (i, (_, msg, sig)) in self.items.iter().enumerate() {
+            if schnorr_challenge(&self.params, &sig.commitment, msg) != Some(sig.verifier_challenge)
+            {
+                return Err(BatchError::InvalidSignature(i));
+            }
+        }
+
+        let weights: Vec<C::ScalarField> = self
+            .items
+            .iter()
+            .map(|_| C::ScalarField::from(rng.gen::<u128>()))
+            .collect();
+
+        match self.find_invalid(&weights, 0..self.items.len()) {
+            Some(i) => Err(BatchError::InvalidSignature(i)),
+            None => Ok(()),
+        }
+    }
+
+    // Bisects `range` down to a signature whose equation doesn't hold. If both halves hold, so
+    // does the whole range, since the weights are the same.
+    fn find_invalid(&self, weights: &[C::ScalarField], range: Range<usize>) -> Option<usize> {
+        if self.combination_holds(weights, range.clone()) {
+            return None;
+        }
+        if range.len() == 1 {
+            return Some(range.start);
+        }
+
+        let mid = range.start + range.len() / 2;
+        self.find_invalid(weights, range.start..mid)
+            .or_else(|| self.find_invalid(weights, mid..range.end))
+    }
+
+    // Whether `Σ zᵢ·(sᵢ·G + eᵢ·pkᵢ - Rᵢ) = 0` over the signatures in `range`
+    fn combination_holds(&self, weights: &[C::ScalarField], range: Range<usize>) -> bool {
+        let items = &self.items[range.clone()];
+        let weights = &weights[range];
+
+        let mut bases = Vec::with_capacity(2 * items.len() + 1);
+        let mut scalars = Vec::with_capacity(2 * items.len() + 1);
+        let mut generator_scalar = C::ScalarField::from(0u8);
+        for ((pk, _, sig), z) in items.iter().zip(weights) {
+            generator_scalar += *z * sig.prover_response;
+            bases.extend([*pk, sig.commitment]);
+            scalars.extend([*z * sig.verifier_challenge, -*z]);
+        }
+        bases.push(self.params.generator);
+        scalars.push(generator_scalar);
+
+        C::msm(&bases, &scalars)
+            .expect("there's a scalar for each base")
+            .is_zero()
+    }
+}
+
+impl<C: CurveGroup> SchnorrSystem<C> {
+    /// An empty batch of signatures to verify under this system's parameters
+    pub fn batch_verifier(&self) -> BatchVerifier<C> {
+        BatchVerifier {
+            params: self.params().clone(),
+            items: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ec::models::twisted_edwards::Projective as TEProjective;
+    use ark_ed_on_bls12_381::{Fr as JubjubFr, JubjubConfig};
+    use ark_ff::UniformRand;
+
+    type Jubjub = TEProjective<JubjubConfig>;
+
+    fn system() -> SchnorrSystem<Jubjub> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    // A batch of `n` valid signatures, each by a different user on a different message
+    fn batch(system: &SchnorrSystem<Jubjub>, n: usize) -> BatchVerifier<Jubjub> {
+        let mut rng = rand::thread_rng();
+        let mut batch = system.batch_verifier();
+        for i in 0..n {
+            let user = system.new_user(&mut rng);
+            let msg = format!("message {i}");
+            batch.queue(
+                &user.pk,
+                msg.as_bytes(),
+                &user.sign_batchable(msg.as_bytes()).unwrap(),
+            );
+        }
+        batch
+    }
+
+    #[test]
+    fn valid_batch() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+
+        assert_eq!(system.batch_verifier().verify(&mut rng), Ok(()));
+        assert_eq!(batch(&system, 1).verify(&mut rng), Ok(()));
+        assert_eq!(batch(&system, 13).verify(&mut rng), Ok(()));
+    }
+
+    #[test]
+    /// Check that a batchable signature is an ordinary signature with its commitment attached
+    fn matches_single_verification() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let sig = user.sign_batchable(b"message").unwrap();
+
+        assert!(system
+            .verify(&user.pk, b"message", &sig.signature())
+            .unwrap());
+        assert_eq!(
+            BatchableSignature::new(system.params(), &user.pk, &sig.signature()),
+            sig
+        );
+
+        let mut bytes = Vec::new();
+        sig.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(
+            BatchableSignature::<Jubjub>::deserialize_compressed(&bytes[..]).unwrap(),
+            sig
+        );
+    }
+
+    #[test]
+    /// Check that a signature whose challenge doesn't match its commitment is found
+    fn wrong_challenge() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+
+        for bad in [0, 4, 9] {
+            let mut batch = batch(&system, 10);
+            batch.items[bad].1 = b"tampered".to_vec();
+            assert_eq!(
+                batch.verify(&mut rng),
+                Err(BatchError::InvalidSignature(bad))
+            );
+        }
+    }
+
+    #[test]
+    /// Check that bisection finds a signature whose challenge matches its commitment, but whose
+    /// response doesn't
+    fn wrong_response() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+
+        for bad in [0, 6, 10] {
+            let mut batch = batch(&system, 11);
+            batch.items[bad].2.prover_response += JubjubFr::from(1u8);
+            assert_eq!(
+                batch.verify(&mut rng),
+                Err(BatchError::InvalidSignature(bad))
+            );
+        }
+    }
+
+    #[test]
+    /// Check that a forgery with a random commitment and its real challenge is caught, whoever's
+    /// key it claims
+    fn forged_commitment() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let mut batch = batch(&system, 7);
+
+        let victim = batch.items[2].0;
+        let (commitment, verifier_challenge) = loop {
+            let r = Jubjub::rand(&mut rng).into_affine();
+            if let Some(e) = schnorr_challenge(system.params(), &r, b"forged") {
+                break (r, e);
+            }
+        };
+        batch.queue(
+            &victim,
+            b"forged",
+            &BatchableSignature {
+                commitment,
+                prover_response: JubjubFr::rand(&mut rng),
+                verifier_challenge,
+            },
+        );
+
+        assert_eq!(batch.verify(&mut rng), Err(BatchError::InvalidSignature(7)));
+    }
+}
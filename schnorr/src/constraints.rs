@@ -0,0 +1,345 @@
+//! Schnorr verification in R1CS, for [`crate::poseidon::PoseidonSchnorr`] signatures on Jubjub
+//! checked inside a SNARK over BLS12-381. The message is the field elements the signature was made
+//! over, e.g. from [`crate::poseidon::message_to_field_elements`].
+
+use ark_crypto_primitives::{
+    signature::schnorr::Signature,
+    sponge::{constraints::CryptographicSpongeVar, poseidon::constraints::PoseidonSpongeVar},
+};
+use ark_ec::{AffineRepr, Group};
+use ark_ed_on_bls12_381::{
+    constraints::EdwardsVar, EdwardsAffine, EdwardsProjective, Fq, Fr as JubjubFr,
+};
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::{
+    alloc::{AllocVar, AllocationMode},
+    boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    groups::CurveVar,
+    R1CSVar, ToBitsGadget,
+};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystemRef, Namespace, Result, SynthesisError,
+};
+use std::borrow::Borrow;
+
+use crate::poseidon::{poseidon_config, CHALLENGE_BITS, CHALLENGE_DOMAIN_SEP};
+
+/// The in-circuit version of [`crate::poseidon::challenge`], as [`CHALLENGE_BITS`] little-endian
+/// bits
+pub fn challenge_gadget(
+    pk: &EdwardsVar,
+    r: &EdwardsVar,
+    msg: &[FpVar<Fq>],
+) -> Result<Vec<Boolean<Fq>>> {
+    let input = [
+        vec![
+            FpVar::constant(Fq::from_le_bytes_mod_order(CHALLENGE_DOMAIN_SEP)),
+            r.x.clone(),
+            r.y.clone(),
+            pk.x.clone(),
+            pk.y.clone(),
+            FpVar::constant(Fq::from(msg.len() as u64)),
+        ],
+        msg.to_vec(),
+    ]
+    .concat();
+
+    let mut sponge = PoseidonSpongeVar::new(pk.cs(), poseidon_config());
+    sponge.absorb(&input)?;
+    let hash = sponge.squeeze_field_elements(1)?.remove(0);
+
+    let mut bits = hash.to_bits_le()?;
+    bits.truncate(CHALLENGE_BITS);
+    Ok(bits)
+}
+
+/// A signature as little-endian bits. The response has as many bits as the Jubjub scalar field,
+/// and the challenge has [`CHALLENGE_BITS`].
+#[derive(Clone)]
+pub struct SignatureVar {
+    pub response: Vec<Boolean<Fq>>,
+    pub challenge: Vec<Boolean<Fq>>,
+}
+
+impl AllocVar<Signature<EdwardsProjective>, Fq> for SignatureVar {
+    fn new_variable<T: Borrow<Signature<EdwardsProjective>>>(
+        cs: impl Into<Namespace<Fq>>,
+        f: impl FnOnce() -> Result<T>,
+        mode: AllocationMode,
+    ) -> Result<Self> {
+        let cs = cs.into().cs();
+        let sig = f().map(|sig| sig.borrow().clone());
+        let bits = |scalar: Result<JubjubFr>, len: usize| {
+            let bits = scalar.map(|s| s.into_bigint().to_bits_le());
+            (0..len)
+                .map(|i| {
+                    Boolean::new_variable(
+                        cs.clone(),
+                        || bits.as_ref().map(|b| b[i]).map_err(|e| *e),
+                        mode,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            response: bits(
+                sig.as_ref().map(|s| s.prover_response).map_err(|e| *e),
+                JubjubFr::MODULUS_BIT_SIZE as usize,
+            )?,
+            challenge: bits(
+                sig.as_ref().map(|s| s.verifier_challenge).map_err(|e| *e),
+                CHALLENGE_BITS,
+            )?,
+        })
+    }
+}
+
+/// The in-circuit version of [`crate::poseidon::verify`]. The generator is a constant of the
+/// circuit, and the public key isn't checked to be in the prime-order subgroup, so it should come
+/// from a set the verifier already trusts.
+pub fn verify_gadget(
+    generator: &EdwardsAffine,
+    pk: &EdwardsVar,
+    msg: &[FpVar<Fq>],
+    sig: &SignatureVar,
+) -> Result<Boolean<Fq>> {
+    // [G, 2G, 4G, ...], one per bit of the response
+    let bases: Vec<EdwardsProjective> = (0..sig.response.len())
+        .scan(generator.into_group(), |base, _| {
+            let current = *base;
+            base.double_in_place();
+            Some(current)
+        })
+        .collect();
+
+    let mut r = EdwardsVar::zero();
+    r.precomputed_base_scalar_mul_le(sig.response.iter().zip(&bases))?;
+    r += pk.scalar_mul_le(sig.challenge.iter())?;
+
+    challenge_gadget(pk, &r, msg)?.is_eq(&sig.challenge)
+}
+
+/// Proves knowledge of a valid signature on a hidden message, by a hidden public key from a
+/// public set.
+///
+/// The public inputs are the coordinates of the keys in the set. See
+/// [`SignatureInSetProof::public_inputs`]. The circuit shape depends on the size of the set and
+/// the length of the message, so each combination needs its own keys. Nobody is in the empty set,
+/// so synthesizing with one fails with [`SynthesisError::Unsatisfiable`].
+#[derive(Clone)]
+pub struct SignatureInSetProof {
+    // Circuit constant
+    pub generator: EdwardsAffine,
+
+    // Public inputs
+    pub public_keys: Vec<EdwardsAffine>,
+
+    // Private witnesses
+    pub public_key: EdwardsAffine,
+    pub msg: Vec<Fq>,
+    pub sig: Signature<EdwardsProjective>,
+}
+
+impl SignatureInSetProof {
+    /// The public inputs a verifier needs to check a proof of this statement
+    pub fn public_inputs(&self) -> Vec<Fq> {
+        self.public_keys
+            .iter()
+            .flat_map(|pk| [pk.x, pk.y])
+            .collect()
+    }
+}
+
+impl ConstraintSynthesizer<Fq> for SignatureInSetProof {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fq>) -> Result<()> {
+        // The verifier knows the keys, so they don't need on-curve or subgroup checks here
+        let public_keys = self
+            .public_keys
+            .iter()
+            .map(|pk| {
+                EdwardsVar::new_variable_omit_on_curve_check(
+                    ark_relations::ns!(cs, "pub key"),
+                    || Ok(*pk),
+                    AllocationMode::Input,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Equal to one of the public keys, so it's a valid point too
+        let public_key = EdwardsVar::new_variable_omit_on_curve_check(
+            ark_relations::ns!(cs, "key"),
+            || Ok(self.public_key),
+            AllocationMode::Witness,
+        )?;
+        let msg =
+            Vec::<FpVar<Fq>>::new_witness(ark_relations::ns!(cs, "msg"), || Ok(&self.msg[..]))?;
+        let sig = SignatureVar::new_witness(ark_relations::ns!(cs, "sig"), || Ok(&self.sig))?;
+
+        verify_gadget(&self.generator, &public_key, &msg, &sig)?.enforce_equal(&Boolean::TRUE)?;
+
+        if public_keys.is_empty() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let in_set = public_keys
+            .iter()
+            .map(|pk| pk.is_eq(&public_key))
+            .collect::<Result<Vec<_>>>()?;
+        Boolean::kary_or(&in_set)?.enforce_equal(&Boolean::TRUE)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        poseidon::{sign, verify},
+        SchnorrSystem,
+    };
+    use ark_bls12_381::Bls12_381;
+    use ark_ec::CurveGroup;
+    use ark_ff::UniformRand;
+    use ark_groth16::Groth16;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_snark::SNARK;
+
+    fn generator() -> EdwardsAffine {
+        SchnorrSystem::<EdwardsProjective>::from_domain(b"schnorr tests")
+            .params()
+            .generator
+    }
+
+    fn msg() -> Vec<Fq> {
+        [1u8, 2, 3].map(Fq::from).to_vec()
+    }
+
+    // A set of `len` keys, and the secret key of the one at `index`
+    fn keys(len: usize, index: usize) -> (Vec<EdwardsAffine>, JubjubFr) {
+        let mut rng = rand::thread_rng();
+        let sks: Vec<JubjubFr> = (0..len).map(|_| JubjubFr::rand(&mut rng)).collect();
+        let pks = sks
+            .iter()
+            .map(|sk| (generator() * sk).into_affine())
+            .collect();
+
+        (pks, sks[index])
+    }
+
+    fn create_new_proof(len: usize, index: usize) -> SignatureInSetProof {
+        let (public_keys, sk) = keys(len, index);
+        let msg = msg();
+
+        SignatureInSetProof {
+            generator: generator(),
+            public_key: public_keys[index],
+            sig: sign(&generator(), &sk, &msg, &mut rand::thread_rng()),
+            public_keys,
+            msg,
+        }
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let (pks, sk) = keys(1, 0);
+        let sig = sign(&generator(), &sk, &msg(), &mut rand::thread_rng());
+
+        assert!(verify(&generator(), &pks[0], &msg(), &sig));
+        assert!(!verify(&generator(), &pks[0], &msg()[1..], &sig));
+        assert!(!verify(&generator(), &keys(1, 0).0[0], &msg(), &sig));
+    }
+
+    #[test]
+    /// Check that the gadget accepts exactly the signatures [`verify`] does
+    fn gadget_matches_native() -> Result<()> {
+        let (pks, sk) = keys(1, 0);
+        let sig = sign(&generator(), &sk, &msg(), &mut rand::thread_rng());
+        let mut forged = sig.clone();
+        forged.prover_response += JubjubFr::from(1u8);
+        let other_msg = [Fq::from(4u8)];
+
+        for (msg, sig) in [(&msg()[..], &sig), (&other_msg, &sig), (&msg(), &forged)] {
+            let cs = ConstraintSystem::new_ref();
+            let pk = EdwardsVar::new_witness(cs.clone(), || Ok(pks[0]))?;
+            let msg_var = Vec::new_witness(cs.clone(), || Ok(msg))?;
+            let sig_var = SignatureVar::new_witness(cs.clone(), || Ok(sig))?;
+
+            let valid = verify_gadget(&generator(), &pk, &msg_var, &sig_var)?;
+            assert_eq!(valid.value()?, verify(&generator(), &pks[0], msg, sig));
+            assert!(cs.is_satisfied()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cs_sat() -> Result<()> {
+        for index in [0, 3] {
+            let cs = ConstraintSystem::new_ref();
+            create_new_proof(4, index).generate_constraints(cs.clone())?;
+
+            assert!(cs.is_satisfied()?, "index = {index}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that the circuit rejects a signature on another message, and a signer outside the set
+    fn cs_unsat() -> Result<()> {
+        let mut proof = create_new_proof(4, 1);
+        proof.msg[0] += Fq::from(1u8);
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+        assert!(!cs.is_satisfied()?);
+
+        let mut proof = create_new_proof(4, 1);
+        let signer = proof.public_key;
+        proof.public_keys.retain(|pk| *pk != signer);
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+        assert!(!cs.is_satisfied()?);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Check that nobody is in the empty set
+    fn cs_empty_set() {
+        let mut proof = create_new_proof(1, 0);
+        proof.public_keys.clear();
+
+        let cs = ConstraintSystem::new_ref();
+
+        assert!(matches!(
+            proof.generate_constraints(cs),
+            Err(SynthesisError::Unsatisfiable)
+        ));
+    }
+
+    #[test]
+    /// Check that a true proof is valid, and doesn't verify against a different set
+    fn pf_complete_and_sound() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let proof = create_new_proof(3, 2);
+
+        let (pk, vk) = Groth16::<Bls12_381>::circuit_specific_setup(proof.clone(), &mut rng)?;
+        let pf = Groth16::<Bls12_381>::prove(&pk, proof.clone(), &mut rng)?;
+
+        assert!(Groth16::<Bls12_381>::verify(
+            &vk,
+            &proof.public_inputs(),
+            &pf
+        )?);
+
+        let mut public_inputs = proof.public_inputs();
+        let other = keys(1, 0).0[0];
+        public_inputs[4..].copy_from_slice(&[other.x, other.y]);
+        assert!(!Groth16::<Bls12_381>::verify(&vk, &public_inputs, &pf)?);
+
+        Ok(())
+    }
+}
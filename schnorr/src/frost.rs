@@ -0,0 +1,960 @@
+//! t-of-n FROST threshold signatures, whose aggregate is an ordinary arkworks `Signature` that
+//! verifies under the group key with [`crate::SchnorrSystem::verify`].
+//!
+//! Keys are split either by a trusted dealer with [`trusted_dealer_keygen`], or without one by
+//! the Pedersen DKG from the FROST paper, in three local steps: [`dkg_part1`] makes the package
+//! everyone broadcasts, [`dkg_part2`] checks the others' packages and makes a secret share for
+//! each of them, and [`dkg_part3`] checks the shares received and builds the [`KeyShare`]. Both
+//! commit to the sharing polynomial with Feldman commitments, so every participant checks their
+//! share.
+//!
+//! Signing takes two rounds. Each signer publishes [`SigningCommitments`] from
+//! [`KeyShare::commit`], and once at least `t` of them and the message are gathered in a
+//! [`SigningPackage`], each signer makes a [`SignatureShare`] with [`KeyShare::sign`].
+//! [`PublicKeyPackage::aggregate`] checks the shares and sums them into the signature. The shares
+//! follow arkworks' convention, `s_i = d_i + ρ_i·e_i - λ_i·e·x_i`.
+
+use ark_crypto_primitives::{
+    crh::sha256::Sha256,
+    signature::schnorr::{Parameters, PublicKey, SecretKey, Signature},
+};
+use ark_ec::CurveGroup;
+use ark_ff::{Field, One, UniformRand};
+use rand::Rng;
+use std::collections::BTreeMap;
+
+use crate::{hash_to_scalar, schnorr_challenge, to_bytes};
+
+const DKG_PROOF_TAG: &[u8] = b"FROST/dkg-proof";
+const BINDING_TAG: &[u8] = b"FROST/binding";
+const MESSAGE_TAG: &[u8] = b"FROST/message";
+
+/// Errors in FROST key generation and signing. Participants are identified by their index, from
+/// 1 to `n`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrostError {
+    /// The threshold has to be between 1 and the number of participants
+    InvalidThreshold {
+        threshold: usize,
+        participants: usize,
+    },
+    /// A participant index that's 0 or larger than the number of participants
+    InvalidIndex(u64),
+    /// A participant's proof of knowledge of their DKG secret is invalid
+    InvalidProofOfKnowledge(u64),
+    /// The secret share from this participant doesn't match their commitment
+    InvalidSecretShare(u64),
+    /// There should be one package from each other participant
+    WrongNumberOfPackages { expected: usize, got: usize },
+    /// Fewer than the threshold of signers took part
+    NotEnoughSigners { threshold: usize, got: usize },
+    /// This participant appears twice
+    DuplicateParticipant(u64),
+    /// This participant isn't one of the signers in the signing package
+    UnknownSigner(u64),
+    /// The signature share from this participant is invalid
+    InvalidSignatureShare(u64),
+    /// The group commitment gives a hash that isn't a scalar, so arkworks wouldn't accept the
+    /// signature. The signers have to start again with fresh nonces.
+    ChallengeOutOfRange,
+}
+
+impl core::fmt::Display for FrostError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FrostError::InvalidThreshold {
+                threshold,
+                participants,
+            } => write!(
+                f,
+                "a threshold of {threshold} doesn't work with {participants} participants"
+            ),
+            FrostError::InvalidIndex(i) => write!(f, "{i} isn't a valid participant index"),
+            FrostError::InvalidProofOfKnowledge(i) => {
+                write!(f, "participant {i}'s proof of knowledge is invalid")
+            }
+            FrostError::InvalidSecretShare(i) => {
+                write!(f, "the secret share from participant {i} is invalid")
+            }
+            FrostError::WrongNumberOfPackages { expected, got } => {
+                write!(f, "expected {expected} packages, got {got}")
+            }
+            FrostError::NotEnoughSigners { threshold, got } => {
+                write!(
+                    f,
+                    "{got} signers is fewer than the threshold of {threshold}"
+                )
+            }
+            FrostError::DuplicateParticipant(i) => write!(f, "participant {i} appears twice"),
+            FrostError::UnknownSigner(i) => write!(f, "participant {i} isn't a signer"),
+            FrostError::InvalidSignatureShare(i) => {
+                write!(f, "the signature share from participant {i} is invalid")
+            }
+            FrostError::ChallengeOutOfRange => write!(
+                f,
+                "the challenge is out of range, start again with fresh nonces"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrostError {}
+
+fn check_threshold(threshold: usize, participants: usize) -> Result<(), FrostError> {
+    if threshold == 0 || threshold > participants {
+        return Err(FrostError::InvalidThreshold {
+            threshold,
+            participants,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_index(index: u64, participants: usize) -> Result<(), FrostError> {
+    if index == 0 || index > participants as u64 {
+        return Err(FrostError::InvalidIndex(index));
+    }
+
+    Ok(())
+}
+
+// Evaluates the polynomial with these coefficients, constant term first, at `x`
+fn evaluate<F: Field>(coefficients: &[F], x: u64) -> F {
+    coefficients
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, c| acc * F::from(x) + c)
+}
+
+// The Lagrange coefficient of `index` for interpolating at 0 from the points at `indices`
+fn lagrange_coefficient<F: Field>(index: u64, indices: &[u64]) -> F {
+    let (num, den) = indices
+        .iter()
+        .filter(|&&j| j != index)
+        .fold((F::one(), F::one()), |(num, den), &j| {
+            (num * F::from(j), den * (F::from(j) - F::from(index)))
+        });
+
+    num * den.inverse().expect("indices are distinct")
+}
+
+/// Feldman commitments to the coefficients of a sharing polynomial, constant term first. The
+/// first one is the commitment to the secret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VssCommitment<C: CurveGroup>(pub Vec<C::Affine>);
+
+impl<C: CurveGroup> VssCommitment<C> {
+    fn new(params: &Parameters<C, Sha256>, coefficients: &[C::ScalarField]) -> Self {
+        let points: Vec<C> = coefficients.iter().map(|c| params.generator * c).collect();
+        Self(C::normalize_batch(&points))
+    }
+
+    /// The public key of the share at `index`, i.e., the polynomial evaluated at `index` "in the
+    /// exponent"
+    pub fn verifying_share(&self, index: u64) -> C {
+        let powers: Vec<C::ScalarField> = (0..self.0.len())
+            .scan(C::ScalarField::one(), |power, _| {
+                let current = *power;
+                *power *= C::ScalarField::from(index);
+                Some(current)
+            })
+            .collect();
+
+        C::msm(&self.0, &powers).expect("there's a power for each commitment")
+    }
+
+    /// Checks that `share` is the polynomial evaluated at `index`
+    pub fn verify_share(
+        &self,
+        params: &Parameters<C, Sha256>,
+        index: u64,
+        share: &C::ScalarField,
+    ) -> bool {
+        params.generator * share == self.verifying_share(index)
+    }
+}
+
+/// A participant's share of the group's secret key
+#[derive(Clone, Debug)]
+pub struct KeyShare<C: CurveGroup> {
+    params: Parameters<C, Sha256>,
+    pub index: u64,
+    pub threshold: usize,
+    pub secret_share: SecretKey<C>,
+    pub verifying_share: PublicKey<C>,
+    pub group_key: PublicKey<C>,
+}
+
+/// What everyone knows after key generation: the group key and the public key of each share,
+/// which is enough to check signature shares and aggregate them
+#[derive(Clone, Debug)]
+pub struct PublicKeyPackage<C: CurveGroup> {
+    params: Parameters<C, Sha256>,
+    pub threshold: usize,
+    pub group_key: PublicKey<C>,
+    pub verifying_shares: BTreeMap<u64, PublicKey<C>>,
+}
+
+impl<C: CurveGroup> PublicKeyPackage<C> {
+    fn from_commitment(
+        params: &Parameters<C, Sha256>,
+        threshold: usize,
+        participants: usize,
+        commitment: &VssCommitment<C>,
+    ) -> Self {
+        let indices: Vec<u64> = (1..=participants as u64).collect();
+        let shares: Vec<C> = indices
+            .iter()
+            .map(|&i| commitment.verifying_share(i))
+            .collect();
+
+        Self {
+            params: params.clone(),
+            threshold,
+            group_key: commitment.0[0],
+            verifying_shares: indices
+                .into_iter()
+                .zip(C::normalize_batch(&shares))
+                .collect(),
+        }
+    }
+}
+
+/// A share dealt by [`trusted_dealer_keygen`], with the commitment to check it against
+#[derive(Clone, Debug)]
+pub struct SecretShare<C: CurveGroup> {
+    pub index: u64,
+    pub share: C::ScalarField,
+    pub commitment: VssCommitment<C>,
+}
+
+impl<C: CurveGroup> SecretShare<C> {
+    /// Checks the share against the dealer's commitment
+    pub fn into_key_share(self, params: &Parameters<C, Sha256>) -> Result<KeyShare<C>, FrostError> {
+        if !self
+            .commitment
+            .verify_share(params, self.index, &self.share)
+        {
+            return Err(FrostError::InvalidSecretShare(self.index));
+        }
+
+        Ok(KeyShare {
+            params: params.clone(),
+            index: self.index,
+            threshold: self.commitment.0.len(),
+            secret_share: SecretKey(self.share),
+            verifying_share: (params.generator * self.share).into_affine(),
+            group_key: self.commitment.0[0],
+        })
+    }
+}
+
+/// Splits a fresh secret key into `participants` shares, any `threshold` of which can sign
+pub fn trusted_dealer_keygen<C: CurveGroup, R: Rng>(
+    params: &Parameters<C, Sha256>,
+    threshold: usize,
+    participants: usize,
+    rng: &mut R,
+) -> Result<(PublicKeyPackage<C>, Vec<SecretShare<C>>), FrostError> {
+    check_threshold(threshold, participants)?;
+
+    let coefficients: Vec<C::ScalarField> =
+        (0..threshold).map(|_| C::ScalarField::rand(rng)).collect();
+    let commitment = VssCommitment::new(params, &coefficients);
+    let shares = (1..=participants as u64)
+        .map(|index| SecretShare {
+            index,
+            share: evaluate(&coefficients, index),
+            commitment: commitment.clone(),
+        })
+        .collect();
+
+    Ok((
+        PublicKeyPackage::from_commitment(params, threshold, participants, &commitment),
+        shares,
+    ))
+}
+
+/// A participant's secret state after [`dkg_part1`]. It isn't `Clone`, and is consumed by
+/// [`dkg_part2`].
+pub struct DkgRound1Secret<C: CurveGroup> {
+    params: Parameters<C, Sha256>,
+    index: u64,
+    participants: usize,
+    coefficients: Vec<C::ScalarField>,
+    commitment: VssCommitment<C>,
+}
+
+/// What each participant broadcasts in the first round of the DKG: the commitment to their
+/// polynomial, and a Schnorr proof that they know its constant term
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DkgRound1Package<C: CurveGroup> {
+    pub index: u64,
+    pub commitment: VssCommitment<C>,
+    pub proof_commitment: C::Affine,
+    pub proof_response: C::ScalarField,
+}
+
+/// A participant's secret state after [`dkg_part2`]
+pub struct DkgRound2Secret<C: CurveGroup> {
+    params: Parameters<C, Sha256>,
+    index: u64,
+    participants: usize,
+    own_share: C::ScalarField,
+    commitment: VssCommitment<C>,
+}
+
+/// The secret share one participant sends another, privately, in the second round of the DKG
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DkgRound2Package<C: CurveGroup> {
+    pub from: u64,
+    pub to: u64,
+    pub share: C::ScalarField,
+}
+
+fn dkg_proof_challenge<C: CurveGroup>(
+    params: &Parameters<C, Sha256>,
+    index: u64,
+    secret_commitment: &C::Affine,
+    proof_commitment: &C::Affine,
+) -> C::ScalarField {
+    hash_to_scalar(
+        DKG_PROOF_TAG,
+        &[
+            &params.salt,
+            &index.to_le_bytes(),
+            &to_bytes(secret_commitment),
+            &to_bytes(proof_commitment),
+        ],
+    )
+}
+
+// Checks that every participant but `index` has exactly one entry, keyed by `from`
+fn check_packages<T>(
+    index: u64,
+    participants: usize,
+    packages: &[T],
+    from: impl Fn(&T) -> u64,
+) -> Result<(), FrostError> {
+    if packages.len() != participants - 1 {
+        return Err(FrostError::WrongNumberOfPackages {
+            expected: participants - 1,
+            got: packages.len(),
+        });
+    }
+    let mut seen = vec![false; participants + 1];
+    for package in packages {
+        let from = from(package);
+        check_index(from, participants)?;
+        if from == index || seen[from as usize] {
+            return Err(FrostError::DuplicateParticipant(from));
+        }
+        seen[from as usize] = true;
+    }
+
+    Ok(())
+}
+
+/// Starts the DKG for the participant at `index`: samples their polynomial and makes the package
+/// they broadcast to everyone else
+pub fn dkg_part1<C: CurveGroup, R: Rng>(
+    params: &Parameters<C, Sha256>,
+    index: u64,
+    threshold: usize,
+    participants: usize,
+    rng: &mut R,
+) -> Result<(DkgRound1Secret<C>, DkgRound1Package<C>), FrostError> {
+    check_threshold(threshold, participants)?;
+    check_index(index, participants)?;
+
+    let coefficients: Vec<C::ScalarField> =
+        (0..threshold).map(|_| C::ScalarField::rand(rng)).collect();
+    let commitment = VssCommitment::new(params, &coefficients);
+
+    let k = C::ScalarField::rand(rng);
+    let proof_commitment = (params.generator * k).into_affine();
+    let c = dkg_proof_challenge(params, index, &commitment.0[0], &proof_commitment);
+    let package = DkgRound1Package {
+        index,
+        commitment: commitment.clone(),
+        proof_commitment,
+        proof_response: k + coefficients[0] * c,
+    };
+
+    Ok((
+        DkgRound1Secret {
+            params: params.clone(),
+            index,
+            participants,
+            coefficients,
+            commitment,
+        },
+        package,
+    ))
+}
+
+/// Checks everyone else's first-round packages, and makes the secret share to send to each of
+/// them
+pub fn dkg_part2<C: CurveGroup>(
+    secret: DkgRound1Secret<C>,
+    round1: &[DkgRound1Package<C>],
+) -> Result<(DkgRound2Secret<C>, Vec<DkgRound2Package<C>>), FrostError> {
+    check_packages(secret.index, secret.participants, round1, |p| p.index)?;
+
+    let threshold = secret.coefficients.len();
+    for package in round1 {
+        if package.commitment.0.len() != threshold {
+            return Err(FrostError::InvalidProofOfKnowledge(package.index));
+        }
+        let c = dkg_proof_challenge(
+            &secret.params,
+            package.index,
+            &package.commitment.0[0],
+            &package.proof_commitment,
+        );
+        if (secret.params.generator * package.proof_response - package.commitment.0[0] * c)
+            .into_affine()
+            != package.proof_commitment
+        {
+            return Err(FrostError::InvalidProofOfKnowledge(package.index));
+        }
+    }
+
+    let shares = round1
+        .iter()
+        .map(|package| DkgRound2Package {
+            from: secret.index,
+            to: package.index,
+            share: evaluate(&secret.coefficients, package.index),
+        })
+        .collect();
+
+    Ok((
+        DkgRound2Secret {
+            own_share: evaluate(&secret.coefficients, secret.index),
+            params: secret.params,
+            index: secret.index,
+            participants: secret.participants,
+            commitment: secret.commitment,
+        },
+        shares,
+    ))
+}
+
+/// Checks the secret shares sent to this participant against the senders' commitments, and
+/// finishes the DKG
+pub fn dkg_part3<C: CurveGroup>(
+    secret: DkgRound2Secret<C>,
+    round1: &[DkgRound1Package<C>],
+    round2: &[DkgRound2Package<C>],
+) -> Result<(KeyShare<C>, PublicKeyPackage<C>), FrostError> {
+    check_packages(secret.index, secret.participants, round1, |p| p.index)?;
+    check_packages(secret.index, secret.participants, round2, |p| p.from)?;
+
+    let mut secret_share = secret.own_share;
+    for package in round2 {
+        let sender = round1
+            .iter()
+            .find(|p| p.index == package.from)
+            .expect("every sender has a first-round package");
+        if package.to != secret.index
+            || !sender
+                .commitment
+                .verify_share(&secret.params, secret.index, &package.share)
+        {
+            return Err(FrostError::InvalidSecretShare(package.from));
+        }
+        secret_share += package.share;
+    }
+
+    // The group's polynomial is the sum of everyone's, so its commitment is the sum of theirs
+    let mut group_commitment: Vec<C> = secret.commitment.0.iter().map(|&c| c.into()).collect();
+    for package in round1 {
+        for (sum, c) in group_commitment.iter_mut().zip(&package.commitment.0) {
+            *sum += c;
+        }
+    }
+    let group_commitment = VssCommitment(C::normalize_batch(&group_commitment));
+    let public = PublicKeyPackage::from_commitment(
+        &secret.params,
+        group_commitment.0.len(),
+        secret.participants,
+        &group_commitment,
+    );
+
+    Ok((
+        KeyShare {
+            params: secret.params.clone(),
+            index: secret.index,
+            threshold: group_commitment.0.len(),
+            secret_share: SecretKey(secret_share),
+            verifying_share: public.verifying_shares[&secret.index],
+            group_key: public.group_key,
+        },
+        public,
+    ))
+}
+
+/// A signer's hiding and binding nonces for one signing session. It isn't `Clone`, and
+/// [`KeyShare::sign`] takes it by value, so that it's only ever used once.
+pub struct SigningNonces<C: CurveGroup> {
+    hiding: C::ScalarField,
+    binding: C::ScalarField,
+    commitments: SigningCommitments<C>,
+}
+
+impl<C: CurveGroup> SigningNonces<C> {
+    /// The commitments to publish in the first round
+    pub fn commitments(&self) -> &SigningCommitments<C> {
+        &self.commitments
+    }
+}
+
+/// The commitments to a signer's nonces, which are exchanged in the first round of signing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SigningCommitments<C: CurveGroup> {
+    pub index: u64,
+    pub hiding: C::Affine,
+    pub binding: C::Affine,
+}
+
+/// The message and the commitments of the signers who'll sign it
+#[derive(Clone, Debug)]
+pub struct SigningPackage<C: CurveGroup> {
+    commitments: Vec<SigningCommitments<C>>,
+    message: Vec<u8>,
+}
+
+impl<C: CurveGroup> SigningPackage<C> {
+    pub fn new(
+        mut commitments: Vec<SigningCommitments<C>>,
+        message: &[u8],
+    ) -> Result<Self, FrostError> {
+        commitments.sort_by_key(|c| c.index);
+        if let Some(pair) = commitments.windows(2).find(|w| w[0].index == w[1].index) {
+            return Err(FrostError::DuplicateParticipant(pair[0].index));
+        }
+
+        Ok(Self {
+            commitments,
+            message: message.to_vec(),
+        })
+    }
+
+    /// The signers' indices, in increasing order
+    pub fn signers(&self) -> Vec<u64> {
+        self.commitments.iter().map(|c| c.index).collect()
+    }
+}
+
+// What everyone derives from the signing package: each signer's binding factor, their
+// Lagrange coefficient, and the challenge
+struct SigningSession<C: CurveGroup> {
+    binding_factors: Vec<C::ScalarField>,
+    lagrange_coefficients: Vec<C::ScalarField>,
+    challenge: C::ScalarField,
+}
+
+impl<C: CurveGroup> SigningSession<C> {
+    fn new(
+        params: &Parameters<C, Sha256>,
+        threshold: usize,
+        group_key: &PublicKey<C>,
+        package: &SigningPackage<C>,
+    ) -> Result<Self, FrostError> {
+        if package.commitments.len() < threshold {
+            return Err(FrostError::NotEnoughSigners {
+                threshold,
+                got: package.commitments.len(),
+            });
+        }
+
+        let encoded_commitments: Vec<u8> = package
+            .commitments
+            .iter()
+            .flat_map(|c| {
+                [
+                    c.index.to_le_bytes().to_vec(),
+                    to_bytes(&c.hiding),
+                    to_bytes(&c.binding),
+                ]
+                .concat()
+            })
+            .collect();
+        let message_hash: C::ScalarField = hash_to_scalar(MESSAGE_TAG, &[&package.message]);
+        let binding_factors: Vec<C::ScalarField> = package
+            .commitments
+            .iter()
+            .map(|c| {
+                hash_to_scalar(
+                    BINDING_TAG,
+                    &[
+                        &to_bytes(group_key),
+                        &c.index.to_le_bytes(),
+                        &to_bytes(&message_hash),
+                        &encoded_commitments,
+                    ],
+                )
+            })
+            .collect();
+
+        let group_commitment = package
+            .commitments
+            .iter()
+            .zip(&binding_factors)
+            .map(|(c, rho)| c.hiding + c.binding * rho)
+            .sum::<C>()
+            .into_affine();
+        let challenge = schnorr_challenge::<C>(params, &group_commitment, &package.message)
+            .ok_or(FrostError::ChallengeOutOfRange)?;
+
+        let signers = package.signers();
+        Ok(Self {
+            binding_factors,
+            lagrange_coefficients: signers
+                .iter()
+                .map(|&i| lagrange_coefficient(i, &signers))
+                .collect(),
+            challenge,
+        })
+    }
+}
+
+impl<C: CurveGroup> KeyShare<C> {
+    /// Samples the nonces for a new signing session
+    pub fn commit<R: Rng>(&self, rng: &mut R) -> SigningNonces<C> {
+        let (hiding, binding) = (C::ScalarField::rand(rng), C::ScalarField::rand(rng));
+        let points = C::normalize_batch(&[
+            self.params.generator * hiding,
+            self.params.generator * binding,
+        ]);
+
+        SigningNonces {
+            hiding,
+            binding,
+            commitments: SigningCommitments {
+                index: self.index,
+                hiding: points[0],
+                binding: points[1],
+            },
+        }
+    }
+
+    /// Signs the package's message with the nonces this signer committed to in it
+    pub fn sign(
+        &self,
+        nonces: SigningNonces<C>,
+        package: &SigningPackage<C>,
+    ) -> Result<SignatureShare<C>, FrostError> {
+        let position = package
+            .commitments
+            .iter()
+            .position(|c| *c == nonces.commitments)
+            .ok_or(FrostError::UnknownSigner(self.index))?;
+        let session = SigningSession::new(&self.params, self.threshold, &self.group_key, package)?;
+
+        let nonce = nonces.hiding + nonces.binding * session.binding_factors[position];
+        let lambda = session.lagrange_coefficients[position];
+        Ok(SignatureShare {
+            index: self.index,
+            share: nonce - lambda * session.challenge * self.secret_share.0,
+        })
+    }
+}
+
+/// A signer's share of the signature, exchanged in the second round of signing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureShare<C: CurveGroup> {
+    pub index: u64,
+    pub share: C::ScalarField,
+}
+
+impl<C: CurveGroup> PublicKeyPackage<C> {
+    /// Checks the signers' shares and combines them into a signature on the package's message
+    /// under the group key. Fails with the index of the first signer whose share is invalid, if
+    /// there is one.
+    pub fn aggregate(
+        &self,
+        package: &SigningPackage<C>,
+        shares: &[SignatureShare<C>],
+    ) -> Result<Signature<C>, FrostError> {
+        let session = SigningSession::new(&self.params, self.threshold, &self.group_key, package)?;
+        if shares.len() != package.commitments.len() {
+            return Err(FrostError::WrongNumberOfPackages {
+                expected: package.commitments.len(),
+                got: shares.len(),
+            });
+        }
+
+        // `s_i·G + λ_i·e·Y_i` has to be signer i's effective nonce
+        for (i, c) in package.commitments.iter().enumerate() {
+            let share = shares
+                .iter()
+                .find(|s| s.index == c.index)
+                .ok_or(FrostError::UnknownSigner(c.index))?;
+            let verifying_share = self
+                .verifying_shares
+                .get(&c.index)
+                .ok_or(FrostError::UnknownSigner(c.index))?;
+
+            let expected = c.hiding + c.binding * session.binding_factors[i];
+            let actual = self.params.generator * share.share
+                + *verifying_share * (session.lagrange_coefficients[i] * session.challenge);
+            if actual != expected {
+                return Err(FrostError::InvalidSignatureShare(c.index));
+            }
+        }
+
+        Ok(Signature {
+            prover_response: shares.iter().map(|s| s.share).sum(),
+            verifier_challenge: session.challenge,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SchnorrSystem;
+    use ark_ec::models::twisted_edwards::Projective as TEProjective;
+    use ark_ed_on_bls12_381::{Fr as JubjubFr, JubjubConfig};
+
+    type Jubjub = TEProjective<JubjubConfig>;
+
+    fn system() -> SchnorrSystem<Jubjub> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    // Runs the DKG for every participant, exchanging the packages in memory
+    fn run_dkg(
+        system: &SchnorrSystem<Jubjub>,
+        threshold: usize,
+        participants: usize,
+    ) -> Vec<(KeyShare<Jubjub>, PublicKeyPackage<Jubjub>)> {
+        let mut rng = rand::thread_rng();
+        let (secrets, round1): (Vec<_>, Vec<_>) = (1..=participants as u64)
+            .map(|i| dkg_part1(system.params(), i, threshold, participants, &mut rng).unwrap())
+            .unzip();
+        let others = |packages: &[DkgRound1Package<Jubjub>], i: u64| -> Vec<_> {
+            packages.iter().filter(|p| p.index != i).cloned().collect()
+        };
+
+        let (secrets, round2): (Vec<_>, Vec<_>) = secrets
+            .into_iter()
+            .map(|s| {
+                let index = s.index;
+                dkg_part2(s, &others(&round1, index)).unwrap()
+            })
+            .unzip();
+        let round2: Vec<_> = round2.into_iter().flatten().collect();
+
+        secrets
+            .into_iter()
+            .map(|s| {
+                let index = s.index;
+                let received: Vec<_> = round2.iter().filter(|p| p.to == index).cloned().collect();
+                dkg_part3(s, &others(&round1, index), &received).unwrap()
+            })
+            .collect()
+    }
+
+    // Has `signers` sign `msg`, starting again if the challenge is out of range
+    fn run_signing(
+        public: &PublicKeyPackage<Jubjub>,
+        signers: &[&KeyShare<Jubjub>],
+        msg: &[u8],
+    ) -> Result<Signature<Jubjub>, FrostError> {
+        let mut rng = rand::thread_rng();
+        loop {
+            let nonces: Vec<_> = signers.iter().map(|s| s.commit(&mut rng)).collect();
+            let package = SigningPackage::new(
+                nonces.iter().map(|n| n.commitments().clone()).collect(),
+                msg,
+            )?;
+
+            let shares: Result<Vec<_>, _> = signers
+                .iter()
+                .zip(nonces)
+                .map(|(s, n)| s.sign(n, &package))
+                .collect();
+            match shares {
+                Ok(shares) => return public.aggregate(&package, &shares),
+                Err(FrostError::ChallengeOutOfRange) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[test]
+    fn lagrange_interpolation() {
+        let mut rng = rand::thread_rng();
+        let coefficients: Vec<JubjubFr> = (0..3).map(|_| JubjubFr::rand(&mut rng)).collect();
+
+        for indices in [[1, 2, 3], [2, 4, 5], [5, 1, 3]] {
+            let interpolated: JubjubFr = indices
+                .iter()
+                .map(|&i| {
+                    lagrange_coefficient::<JubjubFr>(i, &indices) * evaluate(&coefficients, i)
+                })
+                .sum();
+            assert_eq!(interpolated, coefficients[0], "indices = {indices:?}");
+        }
+    }
+
+    #[test]
+    /// Check that any `t` of the dealer's shares sign under the group key, and fewer don't
+    fn dealer_keygen_and_sign() {
+        let system = system();
+        let (public, shares) =
+            trusted_dealer_keygen(system.params(), 3, 5, &mut rand::thread_rng()).unwrap();
+        let shares: Vec<_> = shares
+            .into_iter()
+            .map(|s| s.into_key_share(system.params()).unwrap())
+            .collect();
+
+        for signers in [vec![0, 1, 2], vec![1, 3, 4], vec![0, 1, 2, 3, 4]] {
+            let signers: Vec<_> = signers.iter().map(|&i| &shares[i]).collect();
+            let sig = run_signing(&public, &signers, b"message").unwrap();
+
+            assert!(system.verify(&public.group_key, b"message", &sig).unwrap());
+            assert!(!system.verify(&public.group_key, b"massage", &sig).unwrap());
+        }
+
+        assert_eq!(
+            run_signing(&public, &[&shares[0], &shares[4]], b"message").unwrap_err(),
+            FrostError::NotEnoughSigners {
+                threshold: 3,
+                got: 2
+            }
+        );
+    }
+
+    #[test]
+    /// Check that a share that doesn't match the dealer's commitment is rejected
+    fn dealer_invalid_share() {
+        let system = system();
+        let (_, mut shares) =
+            trusted_dealer_keygen(system.params(), 2, 3, &mut rand::thread_rng()).unwrap();
+        shares[1].share += JubjubFr::from(1u8);
+
+        assert_eq!(
+            shares[1]
+                .clone()
+                .into_key_share(system.params())
+                .unwrap_err(),
+            FrostError::InvalidSecretShare(2)
+        );
+        assert!(
+            trusted_dealer_keygen::<Jubjub, _>(system.params(), 4, 3, &mut rand::thread_rng())
+                .is_err()
+        );
+    }
+
+    #[test]
+    /// Check that a local run of the DKG gives everyone the same group key, and that any `t`
+    /// participants can sign under it
+    fn dkg_and_sign() {
+        let system = system();
+        let results = run_dkg(&system, 2, 4);
+        let public = &results[0].1;
+
+        for (share, other) in &results {
+            assert_eq!(other.group_key, public.group_key);
+            assert_eq!(other.verifying_shares, public.verifying_shares);
+            assert_eq!(share.group_key, public.group_key);
+            assert_eq!(
+                (system.params().generator * share.secret_share.0).into_affine(),
+                share.verifying_share
+            );
+        }
+
+        for signers in [[0, 3], [1, 2]] {
+            let signers: Vec<_> = signers.iter().map(|&i| &results[i].0).collect();
+            let sig = run_signing(public, &signers, b"message").unwrap();
+            assert!(system.verify(&public.group_key, b"message", &sig).unwrap());
+        }
+    }
+
+    #[test]
+    /// Check that the DKG catches a bad proof of knowledge and a bad secret share, and names the
+    /// participant who sent it
+    fn dkg_cheating() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let mut round1 = || -> (Vec<_>, Vec<_>) {
+            (1..=3)
+                .map(|i| dkg_part1::<Jubjub, _>(system.params(), i, 2, 3, &mut rng).unwrap())
+                .unzip()
+        };
+
+        // Participant 3 doesn't know the secret they committed to
+        let (secrets, mut packages) = round1();
+        packages[2].proof_response += JubjubFr::from(1u8);
+        let first = secrets.into_iter().next().unwrap();
+        assert!(matches!(
+            dkg_part2(first, &packages[1..]),
+            Err(FrostError::InvalidProofOfKnowledge(3))
+        ));
+
+        // Participant 3 sends participant 2 a share that doesn't match their commitment
+        let (secrets, packages) = round1();
+        let others = |i: usize| -> Vec<_> {
+            (0..3)
+                .filter(|&j| j != i)
+                .map(|j| packages[j].clone())
+                .collect()
+        };
+        let mut results: Vec<_> = secrets
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| dkg_part2(s, &others(i)).unwrap())
+            .collect();
+        let (second, _) = results.remove(1);
+        let mut received: Vec<_> = results
+            .iter()
+            .flat_map(|(_, shares)| shares.iter().filter(|s| s.to == 2).cloned())
+            .collect();
+        received[1].share += JubjubFr::from(1u8);
+
+        assert!(matches!(
+            dkg_part3(second, &others(1), &received),
+            Err(FrostError::InvalidSecretShare(3))
+        ));
+    }
+
+    #[test]
+    /// Check that aggregation names a signer whose share is invalid
+    fn invalid_signature_share() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let (public, shares) = trusted_dealer_keygen(system.params(), 2, 3, &mut rng).unwrap();
+        let shares: Vec<_> = shares
+            .into_iter()
+            .map(|s| s.into_key_share(system.params()).unwrap())
+            .collect();
+
+        let (package, mut sig_shares) = loop {
+            let nonces: Vec<_> = shares.iter().map(|s| s.commit(&mut rng)).collect();
+            let package = SigningPackage::new(
+                nonces.iter().map(|n| n.commitments().clone()).collect(),
+                b"message",
+            )
+            .unwrap();
+            let sig_shares: Result<Vec<_>, _> = shares
+                .iter()
+                .zip(nonces)
+                .map(|(s, n)| s.sign(n, &package))
+                .collect();
+            if let Ok(sig_shares) = sig_shares {
+                break (package, sig_shares);
+            }
+        };
+
+        sig_shares[2].share += JubjubFr::from(1u8);
+        assert_eq!(
+            public.aggregate(&package, &sig_shares).unwrap_err(),
+            FrostError::InvalidSignatureShare(3)
+        );
+    }
+}
@@ -0,0 +1,543 @@
+//! Hierarchical deterministic keys, derived from a master seed along a path like BIP32, so one
+//! backup of the seed recovers every key. Only Jubjub keys are supported, and every one of them
+//! signs with [`RedJubjub`], for the reasons below.
+//!
+//! Each extended key is a key and a 32-byte chain code. A child's secret key is the parent's plus
+//! a tweak, and its public key is the parent's plus the tweak times the generator, where
+//!
+//! `I = HMAC-SHA512(chain code, data || i)`, and the tweak and the child's chain code are
+//! `HMAC-SHA512(I, 0)`, reduced mod the scalar field, and the first 32 bytes of
+//! `HMAC-SHA512(I, 1)`
+//!
+//! for the child number `i`, big-endian. Unlike BIP32, the tweak is reduced from 64 bytes, since
+//! the Jubjub scalar field is about 2^252 and reducing 32 bytes would be biased. For a hardened
+//! child, `data` is a zero byte and the parent's secret key, so only the holder of the secret key
+//! can derive it. For a normal child, `data` is the parent's compressed public key, so
+//! [`ExtendedPublicKey::derive_child`] gets the same public key from the parent's public key
+//! alone. Anyone with a parent's extended public key and one of its normal children's secret keys
+//! can work out the parent's secret key, so keys that are handed out should be hardened.
+//!
+//! The tweak between a parent and a normal child is public to anyone with the parent's extended
+//! public key, and arkworks' Schnorr challenge doesn't cover the public key. So a Schnorr signature
+//! `(s, e)` by one normal child would be a valid signature `(s - e·(t' - t), e)` by a sibling with
+//! tweak `t'`, or `(s + e·t, e)` by the parent, and a parent's signature `(s, e)` would be a valid
+//! signature `(s - e·t, e)` by each of its normal children. Every key here, the master key and
+//! hardened children included, therefore signs with [`RedJubjub`], whose challenge covers the
+//! public key, through [`ExtendedSecretKey::to_user`]. [`RedJubjub`] is only defined over Jubjub,
+//! which is why the other curves aren't supported.
+
+use ark_crypto_primitives::{
+    crh::sha256::Sha256,
+    signature::schnorr::{Parameters, PublicKey, SecretKey},
+};
+use ark_ec::CurveGroup;
+use ark_ed_on_bls12_381::{EdwardsProjective, Fr as JubjubFr};
+use ark_ff::{PrimeField, Zero};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use std::{fmt, str::FromStr};
+
+use crate::{
+    redjubjub::{RedJubjub, RedJubjubParameters},
+    to_bytes, SchnorrSystem, User,
+};
+
+const MASTER_KEY: &[u8] = b"Schnorr HD seed";
+
+/// The first hardened child number. Child numbers below it are normal.
+pub const HARDENED: u32 = 1 << 31;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HdError {
+    /// Hardened children can only be derived from a secret key
+    HardenedFromPublic,
+    /// A child number that's too large to be hardened, i.e., at least 2^31
+    InvalidIndex(u32),
+    /// The child's key is zero, which happens with negligible probability. BIP32 says to skip to
+    /// the next child number.
+    InvalidChild(ChildNumber),
+    InvalidPath(String),
+    /// Keys can't be more than 255 steps from the master key
+    MaxDepth,
+}
+
+impl fmt::Display for HdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdError::HardenedFromPublic => {
+                write!(f, "hardened children can't be derived from a public key")
+            }
+            HdError::InvalidIndex(i) => write!(f, "{i} is too large for a child number"),
+            HdError::InvalidChild(i) => write!(f, "child {i} is invalid, use the next one"),
+            HdError::InvalidPath(path) => write!(f, "{path:?} isn't a derivation path"),
+            HdError::MaxDepth => write!(f, "keys can't be more than 255 steps deep"),
+        }
+    }
+}
+
+impl std::error::Error for HdError {}
+
+/// The position of a child under its parent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChildNumber {
+    Normal(u32),
+    Hardened(u32),
+}
+
+impl ChildNumber {
+    pub fn normal(index: u32) -> Result<Self, HdError> {
+        match index < HARDENED {
+            true => Ok(ChildNumber::Normal(index)),
+            false => Err(HdError::InvalidIndex(index)),
+        }
+    }
+
+    pub fn hardened(index: u32) -> Result<Self, HdError> {
+        match index < HARDENED {
+            true => Ok(ChildNumber::Hardened(index)),
+            false => Err(HdError::InvalidIndex(index)),
+        }
+    }
+
+    /// The number that goes into the derivation, with the top bit set for hardened children
+    pub fn to_u32(self) -> u32 {
+        match self {
+            ChildNumber::Normal(i) => i,
+            ChildNumber::Hardened(i) => i | HARDENED,
+        }
+    }
+}
+
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChildNumber::Normal(i) => write!(f, "{i}"),
+            ChildNumber::Hardened(i) => write!(f, "{i}'"),
+        }
+    }
+}
+
+/// A path from the master key, written like `m/44'/7/0'`, where `'` or `h` marks a hardened step
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DerivationPath(pub Vec<ChildNumber>);
+
+impl FromStr for DerivationPath {
+    type Err = HdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || HdError::InvalidPath(s.to_string());
+        let mut steps = s.split('/');
+        if steps.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        steps
+            .map(|step| {
+                let (index, hardened) = match step.strip_suffix(['\'', 'h']) {
+                    Some(index) => (index, true),
+                    None => (step, false),
+                };
+                let index = index.parse().map_err(|_| invalid())?;
+                match hardened {
+                    true => ChildNumber::hardened(index),
+                    false => ChildNumber::normal(index),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(DerivationPath)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for step in &self.0 {
+            write!(f, "/{step}")?;
+        }
+        Ok(())
+    }
+}
+
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+
+    mac.finalize().into_bytes().into()
+}
+
+// The tweak and chain code for a key with `chain_code`, from `data` and the child number
+fn tweak_and_chain_code<F: PrimeField>(chain_code: &[u8], data: &[&[u8]]) -> (F, [u8; 32]) {
+    let i = hmac_sha512(chain_code, data);
+    let tweak = F::from_le_bytes_mod_order(&hmac_sha512(&i, &[&[0]]));
+    let chain_code = hmac_sha512(&i, &[&[1]])[..32]
+        .try_into()
+        .expect("slice is 32 bytes");
+
+    (tweak, chain_code)
+}
+
+/// A Jubjub secret key that children can be derived from
+#[derive(Clone, Debug)]
+pub struct ExtendedSecretKey {
+    params: Parameters<EdwardsProjective, Sha256>,
+    pub depth: u8,
+    pub child_number: Option<ChildNumber>,
+    pub chain_code: [u8; 32],
+    pub sk: SecretKey<EdwardsProjective>,
+}
+
+/// A Jubjub public key that normal children's public keys can be derived from
+#[derive(Clone, Debug)]
+pub struct ExtendedPublicKey {
+    params: Parameters<EdwardsProjective, Sha256>,
+    pub depth: u8,
+    pub child_number: Option<ChildNumber>,
+    pub chain_code: [u8; 32],
+    pub pk: PublicKey<EdwardsProjective>,
+}
+
+impl ExtendedSecretKey {
+    /// The master key for `seed`, for users of `system`. The seed should have at least 128 bits
+    /// of entropy.
+    pub fn master(system: &SchnorrSystem<EdwardsProjective>, seed: &[u8]) -> Self {
+        let (sk, chain_code) = tweak_and_chain_code(MASTER_KEY, &[seed]);
+
+        Self {
+            params: system.params().clone(),
+            depth: 0,
+            child_number: None,
+            chain_code,
+            sk: SecretKey(sk),
+        }
+    }
+
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, HdError> {
+        let index = child.to_u32().to_be_bytes();
+        let (tweak, chain_code) = match child {
+            ChildNumber::Hardened(_) => tweak_and_chain_code::<JubjubFr>(
+                &self.chain_code,
+                &[&[0], &to_bytes(&self.sk.0), &index],
+            ),
+            ChildNumber::Normal(_) => {
+                tweak_and_chain_code(&self.chain_code, &[&to_bytes(&self.public_key()), &index])
+            }
+        };
+
+        let sk = self.sk.0 + tweak;
+        if sk.is_zero() {
+            return Err(HdError::InvalidChild(child));
+        }
+
+        Ok(Self {
+            params: self.params.clone(),
+            depth: self.depth.checked_add(1).ok_or(HdError::MaxDepth)?,
+            child_number: Some(child),
+            chain_code,
+            sk: SecretKey(sk),
+        })
+    }
+
+    /// The key at `path`, taken as a path from this key
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, HdError> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, &child| key.derive_child(child))
+    }
+
+    pub fn public_key(&self) -> PublicKey<EdwardsProjective> {
+        (self.params.generator * self.sk.0).into_affine()
+    }
+
+    /// The extended public key, which derives the same public keys for normal children
+    pub fn to_public(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey {
+            params: self.params.clone(),
+            depth: self.depth,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            pk: self.public_key(),
+        }
+    }
+
+    /// The user with this key, signing with [`RedJubjub`] under the system's generator. There's
+    /// no arkworks Schnorr user for any key here, see the [module docs](self).
+    pub fn to_user(&self) -> User<EdwardsProjective, RedJubjub> {
+        User {
+            params: RedJubjubParameters {
+                generator: self.params.generator,
+            },
+            pk: self.public_key(),
+            sk: SecretKey(self.sk.0),
+        }
+    }
+}
+
+impl ExtendedPublicKey {
+    /// The public key of a normal child. Hardened children need the secret key.
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, HdError> {
+        let ChildNumber::Normal(index) = child else {
+            return Err(HdError::HardenedFromPublic);
+        };
+        let (tweak, chain_code) = tweak_and_chain_code::<JubjubFr>(
+            &self.chain_code,
+            &[&to_bytes(&self.pk), &index.to_be_bytes()],
+        );
+
+        let pk = (self.pk + self.params.generator * tweak).into_affine();
+        if pk.is_zero() {
+            return Err(HdError::InvalidChild(child));
+        }
+
+        Ok(Self {
+            params: self.params.clone(),
+            depth: self.depth.checked_add(1).ok_or(HdError::MaxDepth)?,
+            child_number: Some(child),
+            chain_code,
+            pk,
+        })
+    }
+
+    /// The key at `path`, taken as a path from this key. The path can't have hardened steps.
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, HdError> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, &child| key.derive_child(child))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::redjubjub::{self, RedJubjubSignature};
+    use ark_crypto_primitives::signature::{
+        schnorr::{Schnorr, Signature},
+        SignatureScheme,
+    };
+
+    type Jubjub = EdwardsProjective;
+
+    fn system() -> SchnorrSystem<Jubjub> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    fn path(s: &str) -> DerivationPath {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn path_parsing() {
+        assert_eq!(path("m"), DerivationPath(vec![]));
+        assert_eq!(
+            path("m/44'/7/0h"),
+            DerivationPath(vec![
+                ChildNumber::Hardened(44),
+                ChildNumber::Normal(7),
+                ChildNumber::Hardened(0)
+            ])
+        );
+        assert_eq!(path("m/44'/7/0h").to_string(), "m/44'/7/0'");
+
+        for invalid in ["", "44/7", "m/", "m/x", "m/-1", "m/2147483648", "m/1''"] {
+            assert!(matches!(
+                invalid.parse::<DerivationPath>(),
+                Err(HdError::InvalidPath(_)) | Err(HdError::InvalidIndex(_))
+            ));
+        }
+    }
+
+    #[test]
+    /// Check that the same seed and path always give the same key, and that other seeds and paths
+    /// give other keys
+    fn deterministic() {
+        let system = system();
+        let master = ExtendedSecretKey::master(&system, &[7; 32]);
+        let key = master.derive_path(&path("m/1'/2")).unwrap();
+
+        assert_eq!(
+            ExtendedSecretKey::master(&system, &[7; 32])
+                .derive_path(&path("m/1'/2"))
+                .unwrap()
+                .sk
+                .0,
+            key.sk.0
+        );
+        assert_eq!(key.depth, 2);
+        assert_eq!(key.child_number, Some(ChildNumber::Normal(2)));
+
+        let others = [
+            ExtendedSecretKey::master(&system, &[8; 32]).derive_path(&path("m/1'/2")),
+            master.derive_path(&path("m/1'/3")),
+            master.derive_path(&path("m/1/2")),
+            master.derive_path(&path("m/1'/2'")),
+            master.derive_path(&path("m/1'")),
+        ];
+        for other in others {
+            assert_ne!(other.unwrap().sk.0, key.sk.0);
+        }
+    }
+
+    #[test]
+    /// Check that normal children's public keys can be derived from the parent's public key, and
+    /// match the ones derived from the secret key
+    fn public_derivation() {
+        let system = system();
+        let account = ExtendedSecretKey::master(&system, b"public derivation seed")
+            .derive_path(&path("m/44'/0'"))
+            .unwrap();
+        let watcher = account.to_public();
+
+        let from_secret = account.derive_path(&path("m/0/5/9")).unwrap();
+        let from_public = watcher.derive_path(&path("m/0/5/9")).unwrap();
+        assert_eq!(from_public.pk, from_secret.public_key());
+        assert_eq!(from_public.chain_code, from_secret.chain_code);
+
+        assert_eq!(
+            watcher.derive_child(ChildNumber::Hardened(0)).err(),
+            Some(HdError::HardenedFromPublic)
+        );
+        assert_eq!(
+            watcher.derive_path(&path("m/0/1'")).err(),
+            Some(HdError::HardenedFromPublic)
+        );
+    }
+
+    #[test]
+    /// Check that the master key, hardened children and normal children all sign with RedJubjub,
+    /// and that a normal child's signature verifies under the publicly derived key
+    fn derived_users_sign() {
+        let system = system();
+        let master = ExtendedSecretKey::master(&system, b"signing seed");
+        let account = master.derive_path(&path("m/3'")).unwrap();
+        let child = account.derive_child(ChildNumber::Normal(1)).unwrap();
+
+        for key in [&master, &account, &child] {
+            let user = key.to_user();
+            let sig = user.sign(b"message").unwrap();
+            assert!(system.verify_redjubjub(&key.public_key(), b"message", &sig));
+        }
+
+        let sig = child.to_user().sign(b"message").unwrap();
+        let pk = account
+            .to_public()
+            .derive_child(ChildNumber::Normal(1))
+            .unwrap()
+            .pk;
+        assert!(system.verify_redjubjub(&pk, b"message", &sig));
+    }
+
+    #[test]
+    /// Check the related-key forgery that keeps normal children from signing with Schnorr: with
+    /// the parent's extended public key, a child's Schnorr signature moves to its sibling and
+    /// parent, while the same trick on RedJubjub signatures fails
+    fn normal_child_forgery() {
+        let system = system();
+        let parent = ExtendedSecretKey::master(&system, b"forgery seed")
+            .derive_path(&path("m/0'"))
+            .unwrap();
+        let xpub = parent.to_public();
+        let (a, b) = (
+            parent.derive_child(ChildNumber::Normal(1)).unwrap(),
+            parent.derive_child(ChildNumber::Normal(2)).unwrap(),
+        );
+
+        // What the attacker computes from the extended public key alone
+        let tweak = |i: u32| -> JubjubFr {
+            tweak_and_chain_code(&xpub.chain_code, &[&to_bytes(&xpub.pk), &i.to_be_bytes()]).0
+        };
+        let (t_a, t_b) = (tweak(1), tweak(2));
+
+        // Signing with `Schnorr` directly, which no key here does
+        let sig = Schnorr::<Jubjub, Sha256>::sign(
+            system.params(),
+            &a.sk,
+            b"message",
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        let (s, e) = (sig.prover_response, sig.verifier_challenge);
+        let as_sibling = Signature {
+            prover_response: s - e * (t_b - t_a),
+            verifier_challenge: e,
+        };
+        let as_parent = Signature {
+            prover_response: s + e * t_a,
+            verifier_challenge: e,
+        };
+        assert!(system
+            .verify(&b.public_key(), b"message", &as_sibling)
+            .unwrap());
+        assert!(system
+            .verify(&parent.public_key(), b"message", &as_parent)
+            .unwrap());
+
+        let sig = a.to_user().sign(b"message").unwrap();
+        let c = redjubjub::challenge(&sig.r, &a.public_key(), b"message");
+        let as_sibling = RedJubjubSignature {
+            r: sig.r,
+            s: sig.s + c * (t_b - t_a),
+        };
+        let as_parent = RedJubjubSignature {
+            r: sig.r,
+            s: sig.s - c * t_a,
+        };
+        assert!(!system.verify_redjubjub(&b.public_key(), b"message", &as_sibling));
+        assert!(!system.verify_redjubjub(&parent.public_key(), b"message", &as_parent));
+    }
+
+    #[test]
+    /// Check the same forgery from a parent to its normal children, for the master key and a
+    /// hardened child: a parent's Schnorr signature moves to any of its normal children, and its
+    /// RedJubjub signature doesn't
+    fn parent_to_child() {
+        let system = system();
+        let master = ExtendedSecretKey::master(&system, b"transfer seed");
+        let hardened = master.derive_child(ChildNumber::Hardened(0)).unwrap();
+
+        for parent in [master, hardened] {
+            let xpub = parent.to_public();
+            let child = xpub.derive_child(ChildNumber::Normal(4)).unwrap();
+            let t: JubjubFr = tweak_and_chain_code(
+                &xpub.chain_code,
+                &[&to_bytes(&xpub.pk), &4u32.to_be_bytes()],
+            )
+            .0;
+
+            let sig = Schnorr::<Jubjub, Sha256>::sign(
+                system.params(),
+                &parent.sk,
+                b"message",
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+            let as_child = Signature {
+                prover_response: sig.prover_response - sig.verifier_challenge * t,
+                verifier_challenge: sig.verifier_challenge,
+            };
+            assert!(system.verify(&child.pk, b"message", &as_child).unwrap());
+
+            let sig = parent.to_user().sign(b"message").unwrap();
+            let c = redjubjub::challenge(&sig.r, &parent.public_key(), b"message");
+            for s in [sig.s, sig.s + c * t] {
+                let as_child = RedJubjubSignature { r: sig.r, s };
+                assert!(!system.verify_redjubjub(&child.pk, b"message", &as_child));
+            }
+        }
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut key = ExtendedSecretKey::master(&system(), b"deep seed");
+        key.depth = u8::MAX;
+
+        assert_eq!(
+            key.derive_child(ChildNumber::Hardened(0)).err(),
+            Some(HdError::MaxDepth)
+        );
+        assert_eq!(
+            key.to_public().derive_child(ChildNumber::Normal(0)).err(),
+            Some(HdError::MaxDepth)
+        );
+    }
+}
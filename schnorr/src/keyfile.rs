@@ -0,0 +1,509 @@
+//! Key files for the `schnorr` binary.
+//!
+//! A key file starts with a header: the magic bytes `SCHN`, the format [`VERSION`], the
+//! [`KeyCurve::ID`] of the curve and the kind of key. Then comes the [`SchnorrSystem`] the key
+//! belongs to, so signing and verifying pick up the same parameters, and then the key, all
+//! compressed with `ark-serialize`.
+//!
+//! A secret key can be encrypted under a passphrase. The encryption key is derived from the
+//! passphrase and a random salt with Argon2id, and the secret key is sealed with
+//! ChaCha20-Poly1305, authenticating the header and system along with it. The Argon2 memory, time
+//! and parallelism costs are written after the system, as little-endian `u32`s, and authenticated
+//! too, so a file decrypts the same way whatever the `argon2` crate's defaults are.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use ark_crypto_primitives::signature::schnorr::{PublicKey, SecretKey};
+use ark_ec::CurveGroup;
+use ark_ed_on_bls12_381::EdwardsProjective;
+use ark_serialize::{CanonicalDeserialize, SerializationError};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::Rng;
+
+use crate::{to_bytes, SchnorrSystem, User};
+
+const MAGIC: &[u8; 4] = b"SCHN";
+
+/// The version of the key file format this module writes and reads
+pub const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 3;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// The Argon2 costs new files are written with: 19 MiB of memory, 2 passes and 1 lane
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+// The most memory, in KiB, a file may ask for, so that a crafted one can't exhaust it
+const MAX_ARGON2_M_COST: u32 = 1024 * 1024;
+const KDF_PARAMS_LEN: usize = 12;
+
+/// A curve that key files can hold keys on
+pub trait KeyCurve: CurveGroup {
+    /// The byte identifying the curve in the header
+    const ID: u8;
+    const NAME: &'static str;
+}
+
+impl KeyCurve for EdwardsProjective {
+    const ID: u8 = 1;
+    const NAME: &'static str = "jubjub";
+}
+
+impl KeyCurve for ark_bls12_381::G1Projective {
+    const ID: u8 = 2;
+    const NAME: &'static str = "bls12-381";
+}
+
+/// What a key file holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyKind {
+    Public = 0,
+    Secret = 1,
+    EncryptedSecret = 2,
+}
+
+impl KeyKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(KeyKind::Public),
+            1 => Some(KeyKind::Secret),
+            2 => Some(KeyKind::EncryptedSecret),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyFileError {
+    /// The file doesn't start with a key file header
+    NotAKeyFile,
+    UnsupportedVersion(u8),
+    /// The key is on another curve, identified by its [`KeyCurve::ID`]
+    WrongCurve {
+        expected: u8,
+        got: u8,
+    },
+    /// The file holds another kind of key
+    WrongKind {
+        expected: KeyKind,
+        got: KeyKind,
+    },
+    /// The secret key is encrypted, and no passphrase was given
+    PassphraseRequired,
+    /// The passphrase is wrong, or the file has been tampered with
+    DecryptionFailed,
+    /// The Argon2 costs in the file are out of range
+    InvalidKdfParams,
+    Serialization(SerializationError),
+}
+
+impl core::fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KeyFileError::NotAKeyFile => write!(f, "not a key file"),
+            KeyFileError::UnsupportedVersion(v) => {
+                write!(f, "key file version {v} isn't supported")
+            }
+            KeyFileError::WrongCurve { expected, got } => {
+                write!(f, "expected a key on curve {expected}, got curve {got}")
+            }
+            KeyFileError::WrongKind { expected, got } => {
+                write!(f, "expected a {expected:?} key, got a {got:?} key")
+            }
+            KeyFileError::PassphraseRequired => {
+                write!(f, "the secret key is encrypted, a passphrase is required")
+            }
+            KeyFileError::DecryptionFailed => {
+                write!(f, "wrong passphrase, or the key file is corrupted")
+            }
+            KeyFileError::InvalidKdfParams => {
+                write!(f, "the key file's Argon2 parameters are out of range")
+            }
+            KeyFileError::Serialization(e) => write!(f, "invalid key: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyFileError {}
+
+impl From<SerializationError> for KeyFileError {
+    fn from(e: SerializationError) -> Self {
+        KeyFileError::Serialization(e)
+    }
+}
+
+fn header<C: KeyCurve>(kind: KeyKind) -> Vec<u8> {
+    [&MAGIC[..], &[VERSION, C::ID, kind as u8]].concat()
+}
+
+/// The curve ID and kind of key in a key file's header, so a reader can tell which curve to read
+/// it with
+pub fn read_header(bytes: &[u8]) -> Result<(u8, KeyKind), KeyFileError> {
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(KeyFileError::NotAKeyFile);
+    }
+    let [version, curve, kind] = [bytes[4], bytes[5], bytes[6]];
+    if version != VERSION {
+        return Err(KeyFileError::UnsupportedVersion(version));
+    }
+
+    Ok((
+        curve,
+        KeyKind::from_byte(kind).ok_or(KeyFileError::NotAKeyFile)?,
+    ))
+}
+
+// Checks the header, and returns the system and whatever comes after it
+fn read_system<C: KeyCurve>(
+    bytes: &[u8],
+) -> Result<(KeyKind, SchnorrSystem<C>, &[u8]), KeyFileError> {
+    let (curve, kind) = read_header(bytes)?;
+    if curve != C::ID {
+        return Err(KeyFileError::WrongCurve {
+            expected: C::ID,
+            got: curve,
+        });
+    }
+
+    let mut rest = &bytes[HEADER_LEN..];
+    let system = SchnorrSystem::deserialize_compressed(&mut rest)?;
+
+    Ok((kind, system, rest))
+}
+
+// The Argon2 costs as they're written to a file: memory in KiB, passes and lanes
+fn kdf_params_bytes(m_cost: u32, t_cost: u32, p_cost: u32) -> Vec<u8> {
+    [m_cost, t_cost, p_cost]
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect()
+}
+
+fn read_kdf_params(bytes: &[u8]) -> Result<Params, KeyFileError> {
+    let [m_cost, t_cost, p_cost] =
+        [0, 1, 2].map(|i| u32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().expect("4 bytes")));
+    if m_cost > MAX_ARGON2_M_COST {
+        return Err(KeyFileError::InvalidKdfParams);
+    }
+
+    Params::new(m_cost, t_cost, p_cost, Some(Key::default().len()))
+        .map_err(|_| KeyFileError::InvalidKdfParams)
+}
+
+fn encryption_key(passphrase: &[u8], salt: &[u8], params: Params) -> Key {
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .expect("the salt and key have valid lengths");
+    key
+}
+
+/// A public key file for `pk` under `system`
+pub fn write_public_key<C: KeyCurve>(system: &SchnorrSystem<C>, pk: &PublicKey<C>) -> Vec<u8> {
+    [header::<C>(KeyKind::Public), to_bytes(system), to_bytes(pk)].concat()
+}
+
+/// The system and public key in a public key file
+pub fn read_public_key<C: KeyCurve>(
+    bytes: &[u8],
+) -> Result<(SchnorrSystem<C>, PublicKey<C>), KeyFileError> {
+    let (kind, system, mut rest) = read_system(bytes)?;
+    if kind != KeyKind::Public {
+        return Err(KeyFileError::WrongKind {
+            expected: KeyKind::Public,
+            got: kind,
+        });
+    }
+
+    let pk = PublicKey::<C>::deserialize_compressed(&mut rest)?;
+    if !rest.is_empty() {
+        return Err(SerializationError::InvalidData.into());
+    }
+
+    Ok((system, pk))
+}
+
+/// A secret key file for `user`, a user of `system`, encrypted if there's a `passphrase`
+pub fn write_secret_key<C: KeyCurve, R: Rng>(
+    system: &SchnorrSystem<C>,
+    user: &User<C>,
+    passphrase: Option<&[u8]>,
+    rng: &mut R,
+) -> Vec<u8> {
+    let sk = to_bytes(&user.sk.0);
+    let Some(passphrase) = passphrase else {
+        return [header::<C>(KeyKind::Secret), to_bytes(system), sk].concat();
+    };
+
+    let kdf_params = kdf_params_bytes(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST);
+    let prefix = [
+        header::<C>(KeyKind::EncryptedSecret),
+        to_bytes(system),
+        kdf_params.clone(),
+    ]
+    .concat();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let params = read_kdf_params(&kdf_params).expect("the costs are in range");
+    let ciphertext = ChaCha20Poly1305::new(&encryption_key(passphrase, &salt, params))
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &sk,
+                aad: &prefix,
+            },
+        )
+        .expect("encrypting to a Vec doesn't fail");
+
+    [prefix, salt.to_vec(), nonce.to_vec(), ciphertext].concat()
+}
+
+/// The user in a secret key file, with the system they belong to. `passphrase` is only needed if
+/// the key is encrypted.
+pub fn read_secret_key<C: KeyCurve>(
+    bytes: &[u8],
+    passphrase: Option<&[u8]>,
+) -> Result<(SchnorrSystem<C>, User<C>), KeyFileError> {
+    let (kind, system, rest) = read_system(bytes)?;
+    let sk_bytes = match kind {
+        KeyKind::Public => {
+            return Err(KeyFileError::WrongKind {
+                expected: KeyKind::Secret,
+                got: kind,
+            })
+        }
+        KeyKind::Secret => rest.to_vec(),
+        KeyKind::EncryptedSecret => {
+            let passphrase = passphrase.ok_or(KeyFileError::PassphraseRequired)?;
+            if rest.len() < KDF_PARAMS_LEN + SALT_LEN + NONCE_LEN {
+                return Err(KeyFileError::DecryptionFailed);
+            }
+            let aad = &bytes[..bytes.len() - rest.len() + KDF_PARAMS_LEN];
+            let (kdf_params, rest) = rest.split_at(KDF_PARAMS_LEN);
+            let (salt, rest) = rest.split_at(SALT_LEN);
+            let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+            let params = read_kdf_params(kdf_params)?;
+            ChaCha20Poly1305::new(&encryption_key(passphrase, salt, params))
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad,
+                    },
+                )
+                .map_err(|_| KeyFileError::DecryptionFailed)?
+        }
+    };
+
+    let mut sk_bytes = &sk_bytes[..];
+    let sk = C::ScalarField::deserialize_compressed(&mut sk_bytes)?;
+    if !sk_bytes.is_empty() {
+        return Err(SerializationError::InvalidData.into());
+    }
+
+    let pk: C = system.params().generator * sk;
+    let user = User {
+        params: system.params().clone(),
+        pk: pk.into_affine(),
+        sk: SecretKey(sk),
+    };
+
+    Ok((system, user))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bls12_381::G1Projective;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    // A Jubjub key encrypted under "hunter2"
+    const ENCRYPTED_KAT: &str = concat!(
+        "5343484e01010222f00a02365a940c306526ca0fd152acacf81718b3310e49c00efdefa89a626cec",
+        "56c1742cf375bd85d6bcc231dbc3dfc164de2af0a018be2cb042060ac2ebe7221c555623d27abb5d",
+        "6dd7f37e5a294d3839365cfd0bebad7262660e6c01eb98004c0000020000000100000045c143dbb9",
+        "609c22ab855d60925a997603d78a3f20d154abd8ddfa694a4bbc2fe54954070853833176f8763f16",
+        "a700479d4c8693bdc5c961756204eb63a3fb0cd5ab04a3dfad7c02f52d782b",
+    );
+
+    fn system() -> SchnorrSystem<EdwardsProjective> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    #[test]
+    fn public_key_round_trip() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let bytes = write_public_key(&system, &user.pk);
+
+        assert_eq!(
+            read_header(&bytes).unwrap(),
+            (EdwardsProjective::ID, KeyKind::Public)
+        );
+        let (read_system, pk) = read_public_key::<EdwardsProjective>(&bytes).unwrap();
+        assert_eq!(pk, user.pk);
+        assert_eq!(read_system.params().generator, system.params().generator);
+    }
+
+    #[test]
+    /// Check that a secret key read back signs under the system it was written with, and gives
+    /// the same public key
+    fn secret_key_round_trip() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let user = system.new_user(&mut rng);
+        let bytes = write_secret_key(&system, &user, None, &mut rng);
+
+        let (read_system, read_user) = read_secret_key::<EdwardsProjective>(&bytes, None).unwrap();
+        assert_eq!(read_user.pk, user.pk);
+        assert_eq!(read_user.sk.0, user.sk.0);
+
+        let sig = read_user.sign(b"message").unwrap();
+        assert!(read_system.verify(&user.pk, b"message", &sig).unwrap());
+    }
+
+    #[test]
+    fn encrypted_secret_key() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let user = system.new_user(&mut rng);
+        let bytes = write_secret_key(&system, &user, Some(b"hunter2"), &mut rng);
+
+        assert_eq!(read_header(&bytes).unwrap().1, KeyKind::EncryptedSecret);
+        assert!(!bytes
+            .windows(32)
+            .any(|window| window == to_bytes(&user.sk.0)));
+
+        let (_, read_user) =
+            read_secret_key::<EdwardsProjective>(&bytes, Some(b"hunter2")).unwrap();
+        assert_eq!(read_user.sk.0, user.sk.0);
+
+        assert!(matches!(
+            read_secret_key::<EdwardsProjective>(&bytes, None),
+            Err(KeyFileError::PassphraseRequired)
+        ));
+        assert!(matches!(
+            read_secret_key::<EdwardsProjective>(&bytes, Some(b"hunter3")),
+            Err(KeyFileError::DecryptionFailed)
+        ));
+
+        // The header and system are authenticated along with the key
+        let mut tampered = bytes.clone();
+        tampered[HEADER_LEN] ^= 1;
+        assert!(read_secret_key::<EdwardsProjective>(&tampered, Some(b"hunter2")).is_err());
+    }
+
+    #[test]
+    /// Check that the Argon2 costs are authenticated along with the key, and that a file can't
+    /// ask for more memory than the limit
+    fn kdf_params() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let user = system.new_user(&mut rng);
+        let bytes = write_secret_key(&system, &user, Some(b"hunter2"), &mut rng);
+        // The costs come right before the salt, nonce and encrypted key
+        let t_cost_at = bytes.len() - (32 + 16) - NONCE_LEN - SALT_LEN - KDF_PARAMS_LEN + 4;
+        assert_eq!(bytes[t_cost_at], ARGON2_T_COST as u8);
+
+        let mut fewer_passes = bytes.clone();
+        fewer_passes[t_cost_at] -= 1;
+        assert!(matches!(
+            read_secret_key::<EdwardsProjective>(&fewer_passes, Some(b"hunter2")),
+            Err(KeyFileError::DecryptionFailed)
+        ));
+
+        let mut too_much_memory = bytes;
+        too_much_memory[t_cost_at - 1] = 0xff;
+        assert!(matches!(
+            read_secret_key::<EdwardsProjective>(&too_much_memory, Some(b"hunter2")),
+            Err(KeyFileError::InvalidKdfParams)
+        ));
+    }
+
+    #[test]
+    /// Pin an encrypted key file, which has to keep decrypting after dependency updates
+    fn encrypted_secret_key_kat() {
+        let system = system();
+        let mut rng = ChaCha20Rng::from_seed([7; 32]);
+        let user = system.new_user(&mut rng);
+        let bytes = write_secret_key(&system, &user, Some(b"hunter2"), &mut rng);
+
+        let pinned: Vec<u8> = (0..ENCRYPTED_KAT.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&ENCRYPTED_KAT[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(bytes, pinned);
+
+        let (_, read_user) =
+            read_secret_key::<EdwardsProjective>(&pinned, Some(b"hunter2")).unwrap();
+        assert_eq!(read_user.sk.0, user.sk.0);
+    }
+
+    #[test]
+    /// Check that keys are only read as the curve and kind they were written as
+    fn wrong_curve_or_kind() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let user = system.new_user(&mut rng);
+        let public = write_public_key(&system, &user.pk);
+        let secret = write_secret_key(&system, &user, None, &mut rng);
+
+        assert!(matches!(
+            read_public_key::<G1Projective>(&public),
+            Err(KeyFileError::WrongCurve {
+                expected: 2,
+                got: 1
+            })
+        ));
+        assert!(matches!(
+            read_public_key::<EdwardsProjective>(&secret),
+            Err(KeyFileError::WrongKind { .. })
+        ));
+        assert!(matches!(
+            read_secret_key::<EdwardsProjective>(&public, None),
+            Err(KeyFileError::WrongKind { .. })
+        ));
+
+        let bls_system = SchnorrSystem::<G1Projective>::from_domain(b"schnorr tests");
+        let bls_user = bls_system.new_user(&mut rng);
+        let (_, pk) =
+            read_public_key::<G1Projective>(&write_public_key(&bls_system, &bls_user.pk)).unwrap();
+        assert_eq!(pk, bls_user.pk);
+    }
+
+    #[test]
+    fn bad_header() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let bytes = write_public_key(&system, &user.pk);
+
+        assert!(matches!(
+            read_header(b"SCH"),
+            Err(KeyFileError::NotAKeyFile)
+        ));
+        assert!(matches!(
+            read_header(&[b"PGP!", &bytes[4..]].concat()),
+            Err(KeyFileError::NotAKeyFile)
+        ));
+
+        let mut future = bytes.clone();
+        future[4] = VERSION + 1;
+        assert!(matches!(
+            read_header(&future),
+            Err(KeyFileError::UnsupportedVersion(2))
+        ));
+
+        let mut truncated = bytes;
+        truncated.pop();
+        assert!(read_public_key::<EdwardsProjective>(&truncated).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+pub mod batch;
+pub mod constraints;
+pub mod frost;
+pub mod hd;
+pub mod keyfile;
+pub mod musig;
+pub mod poseidon;
+pub mod redjubjub;
+
+use ark_crypto_primitives::{
+    crh::sha256::{digest::Digest, Sha256},
+    signature::{
+        schnorr::{Parameters, PublicKey, Schnorr, SecretKey, Signature},
+        SignatureScheme,
+    },
+    Error,
+};
+use ark_ec::CurveGroup;
+use ark_ff::fields::{Field, PrimeField};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, SerializationError, Valid, Validate,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::{
+    fmt::{self, Debug},
+    io::{Read, Write},
+};
+
+/// A key pair, and the parameters of the system that issued it. By default the user signs with
+/// arkworks' `Schnorr` and SHA-256, but any scheme with the same key types works, e.g.
+/// [`poseidon::PoseidonSchnorr`] or [`redjubjub::RedJubjub`].
+pub struct User<C: CurveGroup, S: SignatureScheme = Schnorr<C, Sha256>> {
+    pub params: S::Parameters,
+    pub pk: PublicKey<C>,
+    pub sk: SecretKey<C>,
+}
+
+impl<C, S> User<C, S>
+where
+    C: CurveGroup,
+    S: SignatureScheme<SecretKey = SecretKey<C>>,
+{
+    /// Signs `msg` under the parameters of the system that issued this user
+    pub fn sign(&self, msg: &[u8]) -> Result<S::Signature, Error> {
+        S::sign(&self.params, &self.sk, msg, &mut rand::thread_rng())
+    }
+}
+
+impl<C, S> Debug for User<C, S>
+where
+    C: CurveGroup,
+    S: SignatureScheme,
+    S::Parameters: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("User")
+            .field("params", &self.params)
+            .field("pk", &self.pk)
+            .field("sk", &self.sk)
+            .finish()
+    }
+}
+
+/// The parameters shared by every user of a Schnorr deployment: the generator and the salt that
+/// goes into every challenge. Signatures only verify under the parameters they were made with,
+/// so all users have to be issued by the same system.
+///
+/// The parameters are derived from a 32-byte seed, so anyone with the seed or the domain string
+/// it came from gets the same ones.
+#[derive(Clone, Debug)]
+pub struct SchnorrSystem<C: CurveGroup> {
+    seed: [u8; 32],
+    params: Parameters<C, Sha256>,
+}
+
+impl<C> SchnorrSystem<C>
+where
+    C: CurveGroup,
+    C::ScalarField: PrimeField,
+{
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        // arkworks only lets us make parameters with `setup`, so we feed it a seeded RNG
+        let params = Schnorr::<C, Sha256>::setup(&mut ChaCha20Rng::from_seed(seed))
+            .expect("setup doesn't fail");
+
+        Self { seed, params }
+    }
+
+    /// Derives the parameters from the SHA-256 hash of `domain`, e.g. the name of the deployment
+    pub fn from_domain(domain: &[u8]) -> Self {
+        Self::from_seed(Sha256::digest(domain).into())
+    }
+
+    pub fn params(&self) -> &Parameters<C, Sha256> {
+        &self.params
+    }
+
+    /// Generates a key pair for a new user of this system
+    pub fn new_user<R: Rng>(&self, rng: &mut R) -> User<C> {
+        let (pk, sk) =
+            Schnorr::<C, Sha256>::keygen(&self.params, rng).expect("keygen doesn't fail");
+
+        User {
+            params: self.params.clone(),
+            pk,
+            sk,
+        }
+    }
+
+    /// Checks that `sig` is a signature on `msg` by the holder of `pk`
+    pub fn verify(&self, pk: &PublicKey<C>, msg: &[u8], sig: &Signature<C>) -> Result<bool, Error> {
+        Schnorr::<C, Sha256>::verify(&self.params, pk, msg, sig)
+    }
+}
+
+/// The challenge arkworks' `Schnorr` derives from the commitment `r`: the SHA-256 hash of the
+/// salt, `r` and `msg`, read as a scalar. It's `None` when the hash isn't a canonical scalar, in
+/// which case arkworks signs again with a fresh nonce. Protocols that build one signature from
+/// several parties' nonces, like [`musig`], have to compute it themselves, so that their
+/// signatures verify under [`SchnorrSystem::verify`].
+pub(crate) fn schnorr_challenge<C: CurveGroup>(
+    params: &Parameters<C, Sha256>,
+    r: &C::Affine,
+    msg: &[u8],
+) -> Option<C::ScalarField> {
+    let mut hash_input = Vec::new();
+    params
+        .salt
+        .serialize_compressed(&mut hash_input)
+        .and_then(|_| r.serialize_compressed(&mut hash_input))
+        .and_then(|_| msg.serialize_compressed(&mut hash_input))
+        .expect("serializing to a Vec doesn't fail");
+
+    C::ScalarField::from_random_bytes(&Sha256::digest(&hash_input))
+}
+
+pub(crate) fn to_bytes(x: &impl CanonicalSerialize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    x.serialize_compressed(&mut bytes)
+        .expect("serializing to a Vec doesn't fail");
+    bytes
+}
+
+// SHA-256 of `tag` and `parts`, reduced to a scalar
+pub(crate) fn hash_to_scalar<F: PrimeField>(tag: &[u8], parts: &[&[u8]]) -> F {
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    for part in parts {
+        hasher.update(part);
+    }
+
+    F::from_le_bytes_mod_order(&hasher.finalize())
+}
+
+// Serialized as the seed, followed by the generator and salt it derives. The generator and salt
+// are there for readers that can't derive them, and are checked against the seed when
+// deserializing.
+impl<C: CurveGroup> CanonicalSerialize for SchnorrSystem<C> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.seed.serialize_with_mode(&mut writer, compress)?;
+        self.params
+            .generator
+            .serialize_with_mode(&mut writer, compress)?;
+        self.params.salt.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.seed.serialized_size(compress)
+            + self.params.generator.serialized_size(compress)
+            + self.params.salt.serialized_size(compress)
+    }
+}
+
+impl<C: CurveGroup> Valid for SchnorrSystem<C> {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.params.generator.check()
+    }
+}
+
+impl<C> CanonicalDeserialize for SchnorrSystem<C>
+where
+    C: CurveGroup,
+    C::ScalarField: PrimeField,
+{
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let seed = <[u8; 32]>::deserialize_with_mode(&mut reader, compress, validate)?;
+        let generator = C::Affine::deserialize_with_mode(&mut reader, compress, validate)?;
+        let salt = <[u8; 32]>::deserialize_with_mode(&mut reader, compress, validate)?;
+
+        let system = Self::from_seed(seed);
+        if system.params.generator != generator || system.params.salt != salt {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(system)
+    }
+}
+
+/// Serializes a signature as its response and then its challenge, both compressed. arkworks
+/// doesn't implement `CanonicalSerialize` for `Signature`.
+pub fn serialize_signature<C: CurveGroup>(
+    sig: &Signature<C>,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut bytes = Vec::new();
+    sig.prover_response.serialize_compressed(&mut bytes)?;
+    sig.verifier_challenge.serialize_compressed(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The inverse of [`serialize_signature`]
+pub fn deserialize_signature<C: CurveGroup>(
+    mut bytes: &[u8],
+) -> Result<Signature<C>, SerializationError> {
+    let prover_response = C::ScalarField::deserialize_compressed(&mut bytes)?;
+    let verifier_challenge = C::ScalarField::deserialize_compressed(&mut bytes)?;
+    if !bytes.is_empty() {
+        return Err(SerializationError::InvalidData);
+    }
+
+    Ok(Signature {
+        prover_response,
+        verifier_challenge,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ec::models::twisted_edwards::Projective as TEProjective;
+    use ark_ed_on_bls12_381::JubjubConfig;
+
+    type Jubjub = TEProjective<JubjubConfig>;
+
+    fn system() -> SchnorrSystem<Jubjub> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    #[test]
+    fn valid_signature() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        assert!(system.verify(&user.pk, b"message", &sig).unwrap());
+    }
+
+    #[test]
+    fn tampered_message() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        assert!(!system.verify(&user.pk, b"massage", &sig).unwrap());
+        assert!(!system.verify(&user.pk, b"message ", &sig).unwrap());
+        assert!(!system.verify(&user.pk, b"", &sig).unwrap());
+    }
+
+    #[test]
+    fn wrong_key() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let (user, other) = (system.new_user(&mut rng), system.new_user(&mut rng));
+        let sig = user.sign(b"message").unwrap();
+
+        assert!(!system.verify(&other.pk, b"message", &sig).unwrap());
+    }
+
+    #[test]
+    fn signature_serialization() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        let bytes = serialize_signature(&sig).unwrap();
+        assert_eq!(bytes.len(), 64);
+
+        let decoded = deserialize_signature(&bytes).unwrap();
+        assert!(system.verify(&user.pk, b"message", &decoded).unwrap());
+
+        assert!(deserialize_signature::<Jubjub>(&bytes[..63]).is_err());
+        assert!(deserialize_signature::<Jubjub>(&[&bytes[..], &[0]].concat()).is_err());
+    }
+
+    #[test]
+    /// Check that users issued by one system can verify each other's signatures, and that
+    /// different domains give different parameters
+    fn shared_params() {
+        let mut rng = rand::thread_rng();
+        let alice = system().new_user(&mut rng);
+        let bob = system().new_user(&mut rng);
+        let sig = alice.sign(b"message").unwrap();
+
+        assert_eq!(alice.params.generator, bob.params.generator);
+        assert_eq!(alice.params.salt, bob.params.salt);
+        assert!(system().verify(&alice.pk, b"message", &sig).unwrap());
+
+        let other = SchnorrSystem::<Jubjub>::from_domain(b"other deployment");
+        assert_ne!(other.params().generator, alice.params.generator);
+        assert!(!other.verify(&alice.pk, b"message", &sig).unwrap());
+    }
+
+    #[test]
+    /// Check that [`schnorr_challenge`] gives the challenge arkworks signs with
+    fn challenge_matches_arkworks() {
+        let system = system();
+        let user = system.new_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        let r = (system.params().generator * sig.prover_response
+            + user.pk * sig.verifier_challenge)
+            .into_affine();
+        assert_eq!(
+            schnorr_challenge::<Jubjub>(system.params(), &r, b"message"),
+            Some(sig.verifier_challenge)
+        );
+    }
+
+    #[test]
+    fn system_serialization() {
+        let system = system();
+
+        let mut bytes = Vec::new();
+        system.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), system.compressed_size());
+
+        let decoded = SchnorrSystem::<Jubjub>::deserialize_compressed(&bytes[..]).unwrap();
+        assert_eq!(decoded.params().generator, system.params().generator);
+        assert_eq!(decoded.params().salt, system.params().salt);
+
+        // A salt that doesn't match the seed
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(SchnorrSystem::<Jubjub>::deserialize_compressed(&bytes[..]).is_err());
+    }
+}
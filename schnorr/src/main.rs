@@ -1,34 +1,225 @@
-use ark_ec::{CurveGroup, models::twisted_edwards::Projective as TEProjective};
-use ark_ed_on_bls12_381::JubjubConfig;
-use ark_ff::fields::PrimeField;
-use ark_crypto_primitives::{crh::sha256::Sha256, signature::{schnorr::{PublicKey, Schnorr, SecretKey}, SignatureScheme}};
-use ark_relations::r1cs::Result;
-
-#[derive(Debug)]
-struct User<C: CurveGroup> {
-    pub pk: PublicKey<C>,
-    pub sk: SecretKey<C>,
-}
-
-impl<C> User<C> 
-where
-    C: CurveGroup,
-    C::ScalarField: PrimeField,
-{
-    pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-
-        let params = Schnorr::<C, Sha256>::setup(&mut rng).unwrap();
-        let (pk, sk) = Schnorr::keygen(&params, &mut rng).unwrap();
-
-        Self { pk, sk }
+use ark_bls12_381::G1Projective;
+use ark_ed_on_bls12_381::EdwardsProjective;
+use clap::{Parser, Subcommand, ValueEnum};
+use schnorr::{
+    deserialize_signature,
+    keyfile::{self, KeyCurve},
+    serialize_signature, SchnorrSystem,
+};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// The environment variable secret keys are encrypted and decrypted with
+const PASSPHRASE_VAR: &str = "SCHNORR_PASSPHRASE";
+
+/// Schnorr keys and signatures. Messages are read from a file, or from stdin for `-`. Output
+/// files are never overwritten.
+#[derive(Parser)]
+#[command(name = "schnorr")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generates a key pair, writing the secret and public keys to their own files
+    Keygen {
+        #[arg(long, value_enum, default_value_t = Curve::Jubjub)]
+        curve: Curve,
+        /// The domain the system's parameters are derived from. Only keys with the same domain
+        /// can check each other's signatures.
+        #[arg(long, default_value = "schnorr")]
+        domain: String,
+        /// Encrypts the secret key under the passphrase in SCHNORR_PASSPHRASE
+        #[arg(long)]
+        encrypt: bool,
+        secret_key: PathBuf,
+        public_key: PathBuf,
+    },
+    /// Signs a message. An encrypted secret key is decrypted with SCHNORR_PASSPHRASE.
+    Sign {
+        secret_key: PathBuf,
+        message: PathBuf,
+        signature: PathBuf,
+    },
+    /// Checks a signature, exiting with an error if it's invalid
+    Verify {
+        public_key: PathBuf,
+        message: PathBuf,
+        signature: PathBuf,
+    },
+    /// Writes the public key file for a secret key file
+    Pubkey {
+        secret_key: PathBuf,
+        public_key: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Curve {
+    Jubjub,
+    #[value(name = "bls12-381")]
+    Bls12_381,
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn passphrase() -> Option<Vec<u8>> {
+    std::env::var_os(PASSPHRASE_VAR).map(|p| p.into_encoded_bytes())
+}
+
+fn read_message(path: &Path) -> Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut msg = Vec::new();
+        io::stdin().read_to_end(&mut msg)?;
+        return Ok(msg);
+    }
+
+    Ok(fs::read(path)?)
+}
+
+// Writes a new file, refusing to overwrite an existing one, which might be the only copy of a key.
+// Secret keys are only readable by their owner.
+fn write_new_file(path: &Path, contents: &[u8], secret: bool) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    if secret {
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
     }
+
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => format!("{} already exists", path.display()).into(),
+        _ => Box::<dyn Error>::from(e),
+    })?;
+    file.write_all(contents)?;
+
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let user = User::<TEProjective<JubjubConfig>>::new();
+fn keygen<C: KeyCurve>(domain: &str, encrypt: bool, sk_path: &Path, pk_path: &Path) -> Result<()> {
+    let passphrase = match encrypt {
+        true => Some(passphrase().ok_or(format!("{PASSPHRASE_VAR} isn't set"))?),
+        false => None,
+    };
 
-    println!("{user:?}");
+    let mut rng = rand::thread_rng();
+    let system = SchnorrSystem::<C>::from_domain(domain.as_bytes());
+    let user = system.new_user(&mut rng);
+
+    // Checked up front, so an existing public key doesn't leave a secret key without it
+    if pk_path.exists() {
+        return Err(format!("{} already exists", pk_path.display()).into());
+    }
+    write_new_file(
+        sk_path,
+        &keyfile::write_secret_key(&system, &user, passphrase.as_deref(), &mut rng),
+        true,
+    )?;
+    write_new_file(
+        pk_path,
+        &keyfile::write_public_key(&system, &user.pk),
+        false,
+    )?;
+    println!("generated a {} key pair", C::NAME);
 
     Ok(())
 }
+
+fn sign<C: KeyCurve>(sk_file: &[u8], msg: &[u8], sig_path: &Path) -> Result<()> {
+    let (_, user) = keyfile::read_secret_key::<C>(sk_file, passphrase().as_deref())?;
+    let sig = user.sign(msg)?;
+    write_new_file(sig_path, &serialize_signature(&sig)?, false)?;
+
+    Ok(())
+}
+
+fn verify<C: KeyCurve>(pk_file: &[u8], msg: &[u8], sig: &[u8]) -> Result<bool> {
+    let (system, pk) = keyfile::read_public_key::<C>(pk_file)?;
+    system.verify(&pk, msg, &deserialize_signature(sig)?)
+}
+
+fn pubkey<C: KeyCurve>(sk_file: &[u8], pk_path: &Path) -> Result<()> {
+    let (system, user) = keyfile::read_secret_key::<C>(sk_file, passphrase().as_deref())?;
+    write_new_file(
+        pk_path,
+        &keyfile::write_public_key(&system, &user.pk),
+        false,
+    )?;
+
+    Ok(())
+}
+
+// Calls `f` with the curve named in a key file's header
+macro_rules! with_curve {
+    ($file:expr, $f:ident($($arg:expr),*)) => {
+        match keyfile::read_header($file)?.0 {
+            EdwardsProjective::ID => $f::<EdwardsProjective>($($arg),*),
+            G1Projective::ID => $f::<G1Projective>($($arg),*),
+            id => Err(format!("unknown curve {id}").into()),
+        }
+    };
+}
+
+fn run(command: Command) -> Result<bool> {
+    match command {
+        Command::Keygen {
+            curve,
+            domain,
+            encrypt,
+            secret_key,
+            public_key,
+        } => match curve {
+            Curve::Jubjub => {
+                keygen::<EdwardsProjective>(&domain, encrypt, &secret_key, &public_key)
+            }
+            Curve::Bls12_381 => keygen::<G1Projective>(&domain, encrypt, &secret_key, &public_key),
+        }?,
+        Command::Sign {
+            secret_key,
+            message,
+            signature,
+        } => {
+            let sk_file = fs::read(secret_key)?;
+            let msg = read_message(&message)?;
+            with_curve!(&sk_file, sign(&sk_file, &msg, &signature))?
+        }
+        Command::Verify {
+            public_key,
+            message,
+            signature,
+        } => {
+            let pk_file = fs::read(public_key)?;
+            let (msg, sig) = (read_message(&message)?, fs::read(signature)?);
+            let valid = with_curve!(&pk_file, verify(&pk_file, &msg, &sig))?;
+            println!("signature {}", if valid { "valid" } else { "invalid" });
+            return Ok(valid);
+        }
+        Command::Pubkey {
+            secret_key,
+            public_key,
+        } => {
+            let sk_file = fs::read(secret_key)?;
+            with_curve!(&sk_file, pubkey(&sk_file, &public_key))?
+        }
+    }
+
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
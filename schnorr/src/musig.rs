@@ -0,0 +1,503 @@
+//! Two-round MuSig2 n-of-n multi-signatures, for [`User`]s of the same [`crate::SchnorrSystem`].
+//!
+//! The signers' keys are aggregated into one key with [`KeyAggContext`], weighting each key by a
+//! hash of the whole key list so that no signer can pick their key to cancel out the others'. In
+//! the first round each [`Signer`] publishes a [`PublicNonce`], and in the second, once everyone's
+//! nonces and the message are known, a [`PartialSignature`]. [`aggregate`] checks the partial
+//! signatures and sums them into an ordinary arkworks `Signature`, which verifies under the
+//! aggregate key with [`crate::SchnorrSystem::verify`].
+//!
+//! The partial signatures follow arkworks' convention, `s_i = k_i - e·a_i·x_i`, where `k_i` is
+//! the signer's effective nonce, `a_i` their key's weight and `e` arkworks' challenge.
+
+use ark_crypto_primitives::{
+    crh::sha256::{digest::Digest, Sha256},
+    signature::schnorr::{Parameters, PublicKey, Signature},
+};
+use ark_ec::CurveGroup;
+use ark_ff::UniformRand;
+use rand::Rng;
+use std::collections::HashSet;
+
+use crate::{hash_to_scalar, schnorr_challenge, to_bytes, User};
+
+const KEY_AGG_TAG: &[u8] = b"MuSig2/keyagg";
+const NONCE_COEF_TAG: &[u8] = b"MuSig2/noncecoef";
+
+/// Errors in a MuSig2 session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MuSigError {
+    /// There are no keys to aggregate
+    NoSigners,
+    /// The same key appears twice in the key list
+    DuplicateKey,
+    /// The signer's key isn't in the key list
+    UnknownSigner,
+    /// There should be one nonce or partial signature per signer
+    WrongLength { expected: usize, got: usize },
+    /// The signer's own nonce isn't where their key is in the nonce list
+    NonceMismatch,
+    /// The signer has already signed with this nonce
+    NonceReuse,
+    /// The partial signature from the signer at this index is invalid
+    InvalidPartialSignature(usize),
+    /// The aggregate nonce gives a hash that isn't a scalar, so arkworks wouldn't accept the
+    /// signature. The signers have to start again with fresh nonces.
+    ChallengeOutOfRange,
+}
+
+impl core::fmt::Display for MuSigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MuSigError::NoSigners => write!(f, "there are no keys to aggregate"),
+            MuSigError::DuplicateKey => write!(f, "a key appears twice in the key list"),
+            MuSigError::UnknownSigner => write!(f, "the signer's key isn't in the key list"),
+            MuSigError::WrongLength { expected, got } => {
+                write!(
+                    f,
+                    "expected one entry for each of {expected} signers, got {got}"
+                )
+            }
+            MuSigError::NonceMismatch => write!(f, "the signer's nonce isn't in its place"),
+            MuSigError::NonceReuse => write!(f, "the signer has already used this nonce"),
+            MuSigError::InvalidPartialSignature(i) => {
+                write!(f, "the partial signature from signer {i} is invalid")
+            }
+            MuSigError::ChallengeOutOfRange => {
+                write!(
+                    f,
+                    "the challenge is out of range, start again with fresh nonces"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MuSigError {}
+
+/// The signers' keys, their weights and the aggregate key. Everyone has to use the keys in the
+/// same order, since the order changes the aggregate key.
+#[derive(Clone, Debug)]
+pub struct KeyAggContext<C: CurveGroup> {
+    params: Parameters<C, Sha256>,
+    public_keys: Vec<PublicKey<C>>,
+    coefficients: Vec<C::ScalarField>,
+    aggregate_key: PublicKey<C>,
+}
+
+// What a session needs once the nonces and the message are known
+struct Session<C: CurveGroup> {
+    nonce_coefficient: C::ScalarField,
+    challenge: C::ScalarField,
+}
+
+impl<C: CurveGroup> KeyAggContext<C> {
+    /// Aggregates `public_keys`, of users issued under `params`
+    pub fn new(
+        params: &Parameters<C, Sha256>,
+        public_keys: Vec<PublicKey<C>>,
+    ) -> Result<Self, MuSigError> {
+        if public_keys.is_empty() {
+            return Err(MuSigError::NoSigners);
+        }
+        if (1..public_keys.len()).any(|i| public_keys[..i].contains(&public_keys[i])) {
+            return Err(MuSigError::DuplicateKey);
+        }
+
+        let key_list = to_bytes(&public_keys);
+        let coefficients: Vec<C::ScalarField> = public_keys
+            .iter()
+            .map(|pk| hash_to_scalar(KEY_AGG_TAG, &[&key_list, &to_bytes(pk)]))
+            .collect();
+        let aggregate_key = C::msm(&public_keys, &coefficients)
+            .expect("there's a coefficient for each key")
+            .into_affine();
+
+        Ok(Self {
+            params: params.clone(),
+            public_keys,
+            coefficients,
+            aggregate_key,
+        })
+    }
+
+    /// The key the aggregated signatures verify under
+    pub fn aggregate_key(&self) -> PublicKey<C> {
+        self.aggregate_key
+    }
+
+    pub fn public_keys(&self) -> &[PublicKey<C>] {
+        &self.public_keys
+    }
+
+    fn session(&self, nonces: &[PublicNonce<C>], msg: &[u8]) -> Result<Session<C>, MuSigError> {
+        if nonces.len() != self.public_keys.len() {
+            return Err(MuSigError::WrongLength {
+                expected: self.public_keys.len(),
+                got: nonces.len(),
+            });
+        }
+
+        let aggregate_nonce = [0, 1].map(|j| nonces.iter().map(|n| n.r[j]).sum::<C>());
+        let nonce_coefficient: C::ScalarField = hash_to_scalar(
+            NONCE_COEF_TAG,
+            &[
+                &to_bytes(&self.aggregate_key),
+                &to_bytes(&C::normalize_batch(&aggregate_nonce)),
+                msg,
+            ],
+        );
+        let r = (aggregate_nonce[0] + aggregate_nonce[1] * nonce_coefficient).into_affine();
+        let challenge =
+            schnorr_challenge::<C>(&self.params, &r, msg).ok_or(MuSigError::ChallengeOutOfRange)?;
+
+        Ok(Session {
+            nonce_coefficient,
+            challenge,
+        })
+    }
+}
+
+/// A signer's two secret nonces for one session. It isn't `Clone`, and [`Signer::sign`] takes it
+/// by value, so that it's only ever used once.
+pub struct SecretNonce<C: CurveGroup> {
+    k: [C::ScalarField; 2],
+    public: PublicNonce<C>,
+}
+
+impl<C: CurveGroup> SecretNonce<C> {
+    /// The nonce to publish in the first round
+    pub fn public(&self) -> &PublicNonce<C> {
+        &self.public
+    }
+}
+
+/// The commitments to a signer's secret nonces, which are exchanged in the first round
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicNonce<C: CurveGroup> {
+    pub r: [C::Affine; 2],
+}
+
+/// A signer's share of the signature, exchanged in the second round
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialSignature<C: CurveGroup>(pub C::ScalarField);
+
+/// One signer's side of the protocol. It remembers which nonces it has signed with, and refuses
+/// to sign with any of them again, since two signatures with the same nonce give away the secret
+/// key.
+///
+/// This only covers the lifetime of one `Signer`. The record is kept in memory, so a new
+/// `Signer`, e.g. in a restarted process or one whose RNG was restored from a snapshot, starts
+/// without it. It catches mistakes within a session, and is no substitute for fresh nonces.
+pub struct Signer<'a, C: CurveGroup> {
+    user: &'a User<C>,
+    ctx: &'a KeyAggContext<C>,
+    index: usize,
+    // Hashes of the public nonces, which identify them in a fixed amount of memory each
+    used_nonces: HashSet<[u8; 32]>,
+}
+
+impl<'a, C: CurveGroup> Signer<'a, C> {
+    pub fn new(user: &'a User<C>, ctx: &'a KeyAggContext<C>) -> Result<Self, MuSigError> {
+        let index = ctx
+            .public_keys
+            .iter()
+            .position(|pk| *pk == user.pk)
+            .ok_or(MuSigError::UnknownSigner)?;
+
+        Ok(Self {
+            user,
+            ctx,
+            index,
+            used_nonces: HashSet::new(),
+        })
+    }
+
+    /// Where this signer's nonce and partial signature go in the lists of all of them
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Samples the nonces for a new session
+    pub fn generate_nonce<R: Rng>(&self, rng: &mut R) -> SecretNonce<C> {
+        let k = [C::ScalarField::rand(rng), C::ScalarField::rand(rng)];
+        let r = C::normalize_batch(&k.map(|k| self.ctx.params.generator * k));
+
+        SecretNonce {
+            k,
+            public: PublicNonce { r: [r[0], r[1]] },
+        }
+    }
+
+    /// Signs `msg` given everyone's public nonces, in the order of the key list
+    pub fn sign(
+        &mut self,
+        nonce: SecretNonce<C>,
+        nonces: &[PublicNonce<C>],
+        msg: &[u8],
+    ) -> Result<PartialSignature<C>, MuSigError> {
+        if nonces.get(self.index) != Some(&nonce.public) {
+            return Err(MuSigError::NonceMismatch);
+        }
+        let nonce_hash: [u8; 32] = Sha256::new()
+            .chain_update(to_bytes(&nonce.public.r[0]))
+            .chain_update(to_bytes(&nonce.public.r[1]))
+            .finalize()
+            .into();
+        if self.used_nonces.contains(&nonce_hash) {
+            return Err(MuSigError::NonceReuse);
+        }
+
+        let session = self.ctx.session(nonces, msg)?;
+        self.used_nonces.insert(nonce_hash);
+
+        let k = nonce.k[0] + nonce.k[1] * session.nonce_coefficient;
+        let a = self.ctx.coefficients[self.index];
+        Ok(PartialSignature(k - session.challenge * a * self.user.sk.0))
+    }
+}
+
+/// Checks everyone's partial signatures on `msg` and combines them into a signature under the
+/// aggregate key. Fails with the index of the first invalid partial signature, if there is one.
+pub fn aggregate<C: CurveGroup>(
+    ctx: &KeyAggContext<C>,
+    nonces: &[PublicNonce<C>],
+    msg: &[u8],
+    partials: &[PartialSignature<C>],
+) -> Result<Signature<C>, MuSigError> {
+    if partials.len() != ctx.public_keys.len() {
+        return Err(MuSigError::WrongLength {
+            expected: ctx.public_keys.len(),
+            got: partials.len(),
+        });
+    }
+    let session = ctx.session(nonces, msg)?;
+
+    // `s_i·G + e·a_i·X_i` has to be signer i's effective nonce
+    for (i, s) in partials.iter().enumerate() {
+        let expected = nonces[i].r[0] + nonces[i].r[1] * session.nonce_coefficient;
+        let actual = ctx.params.generator * s.0
+            + ctx.public_keys[i] * (session.challenge * ctx.coefficients[i]);
+        if actual != expected {
+            return Err(MuSigError::InvalidPartialSignature(i));
+        }
+    }
+
+    Ok(Signature {
+        prover_response: partials.iter().map(|s| s.0).sum(),
+        verifier_challenge: session.challenge,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SchnorrSystem;
+    use ark_crypto_primitives::signature::schnorr::SecretKey;
+    use ark_ec::models::twisted_edwards::Projective as TEProjective;
+    use ark_ed_on_bls12_381::{Fr as JubjubFr, JubjubConfig};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    type Jubjub = TEProjective<JubjubConfig>;
+
+    fn system() -> SchnorrSystem<Jubjub> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    // Runs a whole session, starting again if the challenge is out of range
+    fn run_session(
+        ctx: &KeyAggContext<Jubjub>,
+        signers: &mut [Signer<Jubjub>],
+        msg: &[u8],
+    ) -> Signature<Jubjub> {
+        let mut rng = rand::thread_rng();
+        loop {
+            let secret_nonces: Vec<_> =
+                signers.iter().map(|s| s.generate_nonce(&mut rng)).collect();
+            let nonces: Vec<_> = secret_nonces.iter().map(|n| n.public().clone()).collect();
+
+            let partials: Result<Vec<_>, _> = signers
+                .iter_mut()
+                .zip(secret_nonces)
+                .map(|(signer, nonce)| signer.sign(nonce, &nonces, msg))
+                .collect();
+            match partials {
+                Ok(partials) => return aggregate(ctx, &nonces, msg, &partials).unwrap(),
+                Err(MuSigError::ChallengeOutOfRange) => continue,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn honest_signers() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+
+        for n in [1, 2, 5] {
+            let users: Vec<_> = (0..n).map(|_| system.new_user(&mut rng)).collect();
+            let ctx =
+                KeyAggContext::new(system.params(), users.iter().map(|u| u.pk).collect()).unwrap();
+            let mut signers: Vec<_> = users
+                .iter()
+                .map(|u| Signer::new(u, &ctx).unwrap())
+                .collect();
+
+            let sig = run_session(&ctx, &mut signers, b"treasury action");
+            let agg = ctx.aggregate_key();
+            assert!(
+                system.verify(&agg, b"treasury action", &sig).unwrap(),
+                "n = {n}"
+            );
+            assert!(
+                !system.verify(&agg, b"other action", &sig).unwrap(),
+                "n = {n}"
+            );
+
+            // A second session with fresh nonces works too
+            let sig = run_session(&ctx, &mut signers, b"second action");
+            assert!(
+                system.verify(&agg, b"second action", &sig).unwrap(),
+                "n = {n}"
+            );
+        }
+    }
+
+    #[test]
+    /// Check that the aggregate key depends on the order and the set of keys
+    fn key_aggregation() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let (a, b, c) = (
+            system.new_user(&mut rng).pk,
+            system.new_user(&mut rng).pk,
+            system.new_user(&mut rng).pk,
+        );
+        let agg = |keys: Vec<_>| {
+            KeyAggContext::new(system.params(), keys)
+                .unwrap()
+                .aggregate_key()
+        };
+
+        assert_ne!(agg(vec![a, b]), agg(vec![b, a]));
+        assert_ne!(agg(vec![a, b]), agg(vec![a, b, c]));
+        assert_ne!(agg(vec![a, b]), (a + b).into_affine());
+
+        assert_eq!(
+            KeyAggContext::new(system.params(), vec![]).unwrap_err(),
+            MuSigError::NoSigners
+        );
+        assert_eq!(
+            KeyAggContext::new(system.params(), vec![a, b, a]).unwrap_err(),
+            MuSigError::DuplicateKey
+        );
+    }
+
+    #[test]
+    /// Check that an attacker who picks their key as `x'·G - X_honest` doesn't get an aggregate
+    /// key they know the secret of, as they would if the keys were just added up
+    fn rogue_key() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let honest = system.new_user(&mut rng);
+
+        let x = JubjubFr::rand(&mut rng);
+        let target = (system.params().generator * x).into_affine();
+        let rogue_pk = (target - honest.pk).into_affine();
+        let attacker = User::<Jubjub> {
+            params: system.params().clone(),
+            pk: target,
+            sk: SecretKey(x),
+        };
+
+        // Naive aggregation gives the attacker's key
+        assert_eq!((honest.pk + rogue_pk).into_affine(), target);
+
+        let ctx = KeyAggContext::new(system.params(), vec![honest.pk, rogue_pk]).unwrap();
+        assert_ne!(ctx.aggregate_key(), target);
+
+        // and the attacker's own signatures don't verify under the aggregate key
+        let sig = attacker.sign(b"steal the funds").unwrap();
+        assert!(system.verify(&target, b"steal the funds", &sig).unwrap());
+        assert!(!system
+            .verify(&ctx.aggregate_key(), b"steal the funds", &sig)
+            .unwrap());
+    }
+
+    #[test]
+    /// Check that a signer refuses to sign twice with the same nonce, e.g. if its RNG is reseeded
+    /// with the same seed while it's running
+    fn nonce_reuse() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let (alice, bob) = (system.new_user(&mut rng), system.new_user(&mut rng));
+        let ctx = KeyAggContext::new(system.params(), vec![alice.pk, bob.pk]).unwrap();
+        let mut signer = Signer::new(&alice, &ctx).unwrap();
+        let bob_signer = Signer::new(&bob, &ctx).unwrap();
+
+        // Find a seed whose session has a valid challenge, so the first signature succeeds
+        let bob_nonce = bob_signer.generate_nonce(&mut rng);
+        let seed = (0u8..)
+            .find(|&i| {
+                let nonce = signer.generate_nonce(&mut ChaCha20Rng::from_seed([i; 32]));
+                let nonces = [nonce.public().clone(), bob_nonce.public().clone()];
+                signer.sign(nonce, &nonces, b"first").is_ok()
+            })
+            .unwrap();
+
+        let nonce = signer.generate_nonce(&mut ChaCha20Rng::from_seed([seed; 32]));
+        let nonces = [nonce.public().clone(), bob_nonce.public().clone()];
+        assert_eq!(
+            signer.sign(nonce, &nonces, b"second").unwrap_err(),
+            MuSigError::NonceReuse
+        );
+
+        // A signer also won't sign with a nonce that isn't the one it published
+        let nonce = signer.generate_nonce(&mut rng);
+        let other = signer.generate_nonce(&mut rng);
+        let nonces = [other.public().clone(), bob_nonce.public().clone()];
+        assert_eq!(
+            signer.sign(nonce, &nonces, b"second").unwrap_err(),
+            MuSigError::NonceMismatch
+        );
+    }
+
+    #[test]
+    /// Check that aggregation finds a bad partial signature
+    fn invalid_partial_signature() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let users: Vec<_> = (0..3).map(|_| system.new_user(&mut rng)).collect();
+        let ctx =
+            KeyAggContext::new(system.params(), users.iter().map(|u| u.pk).collect()).unwrap();
+        let mut signers: Vec<_> = users
+            .iter()
+            .map(|u| Signer::new(u, &ctx).unwrap())
+            .collect();
+
+        let (nonces, mut partials) = loop {
+            let secret_nonces: Vec<_> =
+                signers.iter().map(|s| s.generate_nonce(&mut rng)).collect();
+            let nonces: Vec<_> = secret_nonces.iter().map(|n| n.public().clone()).collect();
+            let partials: Result<Vec<_>, _> = signers
+                .iter_mut()
+                .zip(secret_nonces)
+                .map(|(signer, nonce)| signer.sign(nonce, &nonces, b"message"))
+                .collect();
+            if let Ok(partials) = partials {
+                break (nonces, partials);
+            }
+        };
+
+        partials[1].0 += JubjubFr::from(1u8);
+        assert_eq!(
+            aggregate(&ctx, &nonces, b"message", &partials).unwrap_err(),
+            MuSigError::InvalidPartialSignature(1)
+        );
+        assert!(matches!(
+            aggregate(&ctx, &nonces, b"message", &partials[..2]),
+            Err(MuSigError::WrongLength { .. })
+        ));
+    }
+}
@@ -0,0 +1,308 @@
+//! Schnorr signatures on Jubjub whose challenge is a Poseidon hash over the Jubjub base field,
+//! which is the BLS12-381 scalar field. They're checked natively like any other
+//! [`SignatureScheme`], and in a circuit over BLS12-381 with [`crate::constraints`], without
+//! hashing any bytes in the circuit.
+//!
+//! The signatures have the same shape as arkworks' `Schnorr`, a response `s` and a challenge `e`
+//! with `R = s·G + e·pk`, and the same key types, so a [`User`] can sign with either. The
+//! challenge is the low [`CHALLENGE_BITS`] bits of `Poseidon(domain_sep, R, pk, len(msg), msg)`.
+
+use ark_crypto_primitives::{
+    signature::{
+        schnorr::{SecretKey, Signature},
+        SignatureScheme,
+    },
+    sponge::{
+        poseidon::{find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge},
+        CryptographicSponge, FieldBasedCryptographicSponge,
+    },
+    Error,
+};
+use ark_ec::CurveGroup;
+use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsProjective, Fq, Fr as JubjubFr};
+use ark_ff::{BigInteger, PrimeField, UniformRand};
+use rand::Rng;
+use std::sync::OnceLock;
+
+use crate::{SchnorrSystem, User};
+
+/// The number of bits of the Poseidon output kept as the challenge. It's one less than the size
+/// of the Jubjub scalar field, so every challenge is a canonical scalar.
+pub const CHALLENGE_BITS: usize = 251;
+
+pub(crate) const CHALLENGE_DOMAIN_SEP: &[u8] = b"schnorr challenge";
+
+/// The number of message bytes packed into each field element
+const BYTES_PER_ELEMENT: usize = 31;
+
+// Poseidon with a width of 3 and x^5, with the round numbers the Poseidon paper gives for a
+// 255-bit field at 128 bits of security
+pub(crate) fn poseidon_config() -> &'static PoseidonConfig<Fq> {
+    static CONFIG: OnceLock<PoseidonConfig<Fq>> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let (full_rounds, partial_rounds, rate) = (8, 57, 2);
+        let (ark, mds) = find_poseidon_ark_and_mds::<Fq>(
+            Fq::MODULUS_BIT_SIZE as u64,
+            rate,
+            full_rounds,
+            partial_rounds,
+            0,
+        );
+        PoseidonConfig::new(
+            full_rounds as usize,
+            partial_rounds as usize,
+            5,
+            mds,
+            ark,
+            rate,
+            1,
+        )
+    })
+}
+
+fn challenge_input(pk: &EdwardsAffine, r: &EdwardsAffine, msg: &[Fq]) -> Vec<Fq> {
+    [
+        vec![
+            Fq::from_le_bytes_mod_order(CHALLENGE_DOMAIN_SEP),
+            r.x,
+            r.y,
+            pk.x,
+            pk.y,
+            Fq::from(msg.len() as u64),
+        ],
+        msg.to_vec(),
+    ]
+    .concat()
+}
+
+/// The challenge for a signature by `pk` on `msg` with commitment `r`
+pub fn challenge(pk: &EdwardsAffine, r: &EdwardsAffine, msg: &[Fq]) -> JubjubFr {
+    let mut sponge = PoseidonSponge::new(poseidon_config());
+    sponge.absorb(&challenge_input(pk, r, msg));
+    let hash: Fq = sponge.squeeze_native_field_elements(1)[0];
+
+    let bits = hash.into_bigint().to_bits_le();
+    JubjubFr::from_bigint(BigInteger::from_bits_le(&bits[..CHALLENGE_BITS]))
+        .expect("challenge is smaller than the modulus")
+}
+
+/// Signs a message of field elements, so that [`verify`] and [`crate::constraints::verify_gadget`]
+/// accept it under `sk·generator`
+pub fn sign<R: Rng>(
+    generator: &EdwardsAffine,
+    sk: &JubjubFr,
+    msg: &[Fq],
+    rng: &mut R,
+) -> Signature<EdwardsProjective> {
+    let pk = (*generator * sk).into_affine();
+    let k = JubjubFr::rand(rng);
+    let r = (*generator * k).into_affine();
+    let e = challenge(&pk, &r, msg);
+
+    // `R = s·G + e·pk`, as in arkworks' `Schnorr`
+    Signature {
+        prover_response: k - e * sk,
+        verifier_challenge: e,
+    }
+}
+
+/// Checks a signature made with [`sign`]
+pub fn verify(
+    generator: &EdwardsAffine,
+    pk: &EdwardsAffine,
+    msg: &[Fq],
+    sig: &Signature<EdwardsProjective>,
+) -> bool {
+    let r = (*generator * sig.prover_response + *pk * sig.verifier_challenge).into_affine();
+
+    challenge(pk, &r, msg) == sig.verifier_challenge
+}
+
+/// The field elements a byte message is signed as: its length in bytes, followed by the bytes
+/// packed little-endian, [`BYTES_PER_ELEMENT`] to an element. This is the message a circuit
+/// verifying a [`PoseidonSchnorr`] signature has to be given.
+pub fn message_to_field_elements(msg: &[u8]) -> Vec<Fq> {
+    // The length keeps messages that only differ by trailing zeros apart
+    std::iter::once(Fq::from(msg.len() as u64))
+        .chain(
+            msg.chunks(BYTES_PER_ELEMENT)
+                .map(Fq::from_le_bytes_mod_order),
+        )
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoseidonParameters {
+    pub generator: EdwardsAffine,
+}
+
+/// [`sign`] and [`verify`] as a [`SignatureScheme`] over byte messages, which are converted with
+/// [`message_to_field_elements`]
+pub struct PoseidonSchnorr;
+
+impl SignatureScheme for PoseidonSchnorr {
+    type Parameters = PoseidonParameters;
+    type PublicKey = EdwardsAffine;
+    type SecretKey = SecretKey<EdwardsProjective>;
+    type Signature = Signature<EdwardsProjective>;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(PoseidonParameters {
+            generator: EdwardsProjective::rand(rng).into_affine(),
+        })
+    }
+
+    fn keygen<R: Rng>(
+        pp: &Self::Parameters,
+        rng: &mut R,
+    ) -> Result<(Self::PublicKey, Self::SecretKey), Error> {
+        let sk = JubjubFr::rand(rng);
+        Ok(((pp.generator * sk).into_affine(), SecretKey(sk)))
+    }
+
+    fn sign<R: Rng>(
+        pp: &Self::Parameters,
+        sk: &Self::SecretKey,
+        message: &[u8],
+        rng: &mut R,
+    ) -> Result<Self::Signature, Error> {
+        Ok(sign(
+            &pp.generator,
+            &sk.0,
+            &message_to_field_elements(message),
+            rng,
+        ))
+    }
+
+    fn verify(
+        pp: &Self::Parameters,
+        pk: &Self::PublicKey,
+        message: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<bool, Error> {
+        Ok(verify(
+            &pp.generator,
+            pk,
+            &message_to_field_elements(message),
+            signature,
+        ))
+    }
+
+    // The challenge commits to the public key, so a signature can't be moved to another key
+    fn randomize_public_key(
+        _pp: &Self::Parameters,
+        _public_key: &Self::PublicKey,
+        _randomness: &[u8],
+    ) -> Result<Self::PublicKey, Error> {
+        Err("PoseidonSchnorr doesn't support key randomization".into())
+    }
+
+    fn randomize_signature(
+        _pp: &Self::Parameters,
+        _signature: &Self::Signature,
+        _randomness: &[u8],
+    ) -> Result<Self::Signature, Error> {
+        Err("PoseidonSchnorr doesn't support signature randomization".into())
+    }
+}
+
+impl SchnorrSystem<EdwardsProjective> {
+    /// The parameters for [`PoseidonSchnorr`] signatures in this system, which share its generator
+    pub fn poseidon_params(&self) -> PoseidonParameters {
+        PoseidonParameters {
+            generator: self.params().generator,
+        }
+    }
+
+    /// Generates a key pair for a new user of this system that signs with [`PoseidonSchnorr`]
+    pub fn new_poseidon_user<R: Rng>(
+        &self,
+        rng: &mut R,
+    ) -> User<EdwardsProjective, PoseidonSchnorr> {
+        let params = self.poseidon_params();
+        let (pk, sk) = PoseidonSchnorr::keygen(&params, rng).expect("keygen doesn't fail");
+
+        User { params, pk, sk }
+    }
+
+    /// Checks that `sig` is a [`PoseidonSchnorr`] signature on `msg` by the holder of `pk`
+    pub fn verify_poseidon(
+        &self,
+        pk: &EdwardsAffine,
+        msg: &[u8],
+        sig: &Signature<EdwardsProjective>,
+    ) -> bool {
+        verify(
+            &self.params().generator,
+            pk,
+            &message_to_field_elements(msg),
+            sig,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constraints::SignatureInSetProof;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, Result};
+
+    fn system() -> SchnorrSystem<EdwardsProjective> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let system = system();
+        let user = system.new_poseidon_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        assert!(system.verify_poseidon(&user.pk, b"message", &sig));
+        assert!(!system.verify_poseidon(&user.pk, b"massage", &sig));
+        assert!(!system.verify_poseidon(
+            &system.new_user(&mut rand::thread_rng()).pk,
+            b"message",
+            &sig
+        ));
+
+        // Signatures of the two schemes aren't interchangeable
+        assert!(!system.verify(&user.pk, b"message", &sig).unwrap());
+    }
+
+    #[test]
+    fn message_packing() {
+        assert_eq!(message_to_field_elements(b""), [Fq::from(0u8)]);
+        assert_eq!(message_to_field_elements(&[7; 31]).len(), 2);
+        assert_eq!(message_to_field_elements(&[7; 32]).len(), 3);
+
+        // Trailing zeros change the length
+        assert_ne!(
+            message_to_field_elements(b"ab"),
+            message_to_field_elements(b"ab\0")
+        );
+    }
+
+    #[test]
+    /// Check that a signature made natively satisfies the circuit, given the packed message
+    fn signature_in_circuit() -> Result<()> {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let user = system.new_poseidon_user(&mut rng);
+        let other = system.new_poseidon_user(&mut rng);
+        let sig = user.sign(b"message").unwrap();
+
+        let proof = SignatureInSetProof {
+            generator: system.params().generator,
+            public_keys: vec![other.pk, user.pk],
+            public_key: user.pk,
+            msg: message_to_field_elements(b"message"),
+            sig,
+        };
+        let cs = ConstraintSystem::new_ref();
+        proof.generate_constraints(cs.clone())?;
+
+        assert!(cs.is_satisfied()?);
+
+        Ok(())
+    }
+}
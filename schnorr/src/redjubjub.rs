@@ -0,0 +1,299 @@
+//! RedJubjub-style signatures, as in the Zcash protocol spec, with re-randomizable keys.
+//!
+//! A signature is `(R, S)` with `R = r·G` and `S = r + c·sk`, where `c = H*(R || pk || msg)`, and
+//! it's accepted if `8·(S·G - R - c·pk)` is the identity. The nonce is `r = H*(T || msg)` for 80
+//! random bytes `T`, so a weak RNG doesn't leak the key by itself. `H*` is SHA-512 with a
+//! personalization prefix, reduced mod the Jubjub scalar field, so this doesn't interoperate with
+//! Zcash, which uses BLAKE2b and its own generator.
+//!
+//! Re-randomizing a key pair by `α` gives `pk' = pk + α·G` and `sk' = sk + α`. Signatures under
+//! `pk'` verify like any other, and without `α`, telling that `pk'` came from `pk` means finding
+//! the discrete log of `pk' - pk`.
+
+use ark_crypto_primitives::{
+    signature::{schnorr::SecretKey, SignatureScheme},
+    Error,
+};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ed_on_bls12_381::{EdwardsAffine, EdwardsProjective, Fr as JubjubFr};
+use ark_ff::{PrimeField, UniformRand, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::Rng;
+use sha2::{Digest, Sha512};
+
+use crate::{SchnorrSystem, User};
+
+const H_STAR_PERSONALIZATION: &[u8] = b"RedJubjubH";
+
+/// The number of random bytes hashed into each nonce
+const NONCE_RANDOMNESS_BYTES: usize = 80;
+
+// H*: SHA-512 of the personalization and `parts`, as a scalar
+fn hash_to_scalar(parts: &[&[u8]]) -> JubjubFr {
+    let mut hasher = Sha512::new();
+    hasher.update(H_STAR_PERSONALIZATION);
+    for part in parts {
+        hasher.update(part);
+    }
+
+    JubjubFr::from_le_bytes_mod_order(&hasher.finalize())
+}
+
+fn point_bytes(p: &EdwardsAffine) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    p.serialize_compressed(&mut bytes)
+        .expect("serializing to a Vec doesn't fail");
+    bytes
+}
+
+pub(crate) fn challenge(r: &EdwardsAffine, pk: &EdwardsAffine, msg: &[u8]) -> JubjubFr {
+    hash_to_scalar(&[&point_bytes(r), &point_bytes(pk), msg])
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct RedJubjubSignature {
+    pub r: EdwardsAffine,
+    pub s: JubjubFr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedJubjubParameters {
+    pub generator: EdwardsAffine,
+}
+
+/// `pk + α·G`, the public half of a key pair re-randomized by `alpha`
+pub fn randomize_public_key(
+    params: &RedJubjubParameters,
+    pk: &EdwardsAffine,
+    alpha: &JubjubFr,
+) -> EdwardsAffine {
+    (*pk + params.generator * alpha).into_affine()
+}
+
+/// `sk + α`, the secret half of a key pair re-randomized by `alpha`
+pub fn randomize_secret_key(
+    sk: &SecretKey<EdwardsProjective>,
+    alpha: &JubjubFr,
+) -> SecretKey<EdwardsProjective> {
+    SecretKey(sk.0 + alpha)
+}
+
+pub struct RedJubjub;
+
+impl SignatureScheme for RedJubjub {
+    type Parameters = RedJubjubParameters;
+    type PublicKey = EdwardsAffine;
+    type SecretKey = SecretKey<EdwardsProjective>;
+    type Signature = RedJubjubSignature;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(RedJubjubParameters {
+            generator: EdwardsProjective::rand(rng).into_affine(),
+        })
+    }
+
+    fn keygen<R: Rng>(
+        pp: &Self::Parameters,
+        rng: &mut R,
+    ) -> Result<(Self::PublicKey, Self::SecretKey), Error> {
+        let sk = JubjubFr::rand(rng);
+        Ok(((pp.generator * sk).into_affine(), SecretKey(sk)))
+    }
+
+    fn sign<R: Rng>(
+        pp: &Self::Parameters,
+        sk: &Self::SecretKey,
+        message: &[u8],
+        rng: &mut R,
+    ) -> Result<Self::Signature, Error> {
+        let pk = (pp.generator * sk.0).into_affine();
+
+        let mut t = [0u8; NONCE_RANDOMNESS_BYTES];
+        rng.fill_bytes(&mut t);
+        let nonce = hash_to_scalar(&[&t, message]);
+
+        let r = (pp.generator * nonce).into_affine();
+        let s = nonce + challenge(&r, &pk, message) * sk.0;
+
+        Ok(RedJubjubSignature { r, s })
+    }
+
+    fn verify(
+        pp: &Self::Parameters,
+        pk: &Self::PublicKey,
+        message: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<bool, Error> {
+        let RedJubjubSignature { r, s } = signature;
+        let c = challenge(r, pk, message);
+
+        // Multiplying by the cofactor ignores any small-order components of `R` and `pk`
+        let check = pp.generator * s - r - *pk * c;
+        Ok(check.into_affine().mul_by_cofactor_to_group().is_zero())
+    }
+
+    /// Re-randomizes `public_key` by `randomness`, read as a little-endian scalar. See
+    /// [`randomize_public_key`].
+    fn randomize_public_key(
+        pp: &Self::Parameters,
+        public_key: &Self::PublicKey,
+        randomness: &[u8],
+    ) -> Result<Self::PublicKey, Error> {
+        Ok(randomize_public_key(
+            pp,
+            public_key,
+            &JubjubFr::from_le_bytes_mod_order(randomness),
+        ))
+    }
+
+    // `S` depends on the nonce, so a signature can only be moved to a re-randomized key by
+    // signing again with the re-randomized secret key
+    fn randomize_signature(
+        _pp: &Self::Parameters,
+        _signature: &Self::Signature,
+        _randomness: &[u8],
+    ) -> Result<Self::Signature, Error> {
+        Err("RedJubjub signatures can't be randomized, sign with the randomized key".into())
+    }
+}
+
+impl User<EdwardsProjective, RedJubjub> {
+    /// The same user under a key pair re-randomized by `alpha`. Nobody who doesn't know `alpha`
+    /// can link the new public key to the old one.
+    pub fn rerandomize(&self, alpha: &JubjubFr) -> Self {
+        User {
+            params: self.params.clone(),
+            pk: randomize_public_key(&self.params, &self.pk, alpha),
+            sk: randomize_secret_key(&self.sk, alpha),
+        }
+    }
+}
+
+impl SchnorrSystem<EdwardsProjective> {
+    /// The parameters for [`RedJubjub`] signatures in this system, which share its generator
+    pub fn redjubjub_params(&self) -> RedJubjubParameters {
+        RedJubjubParameters {
+            generator: self.params().generator,
+        }
+    }
+
+    /// Generates a key pair for a new user of this system that signs with [`RedJubjub`]
+    pub fn new_redjubjub_user<R: Rng>(&self, rng: &mut R) -> User<EdwardsProjective, RedJubjub> {
+        let params = self.redjubjub_params();
+        let (pk, sk) = RedJubjub::keygen(&params, rng).expect("keygen doesn't fail");
+
+        User { params, pk, sk }
+    }
+
+    /// Checks that `sig` is a [`RedJubjub`] signature on `msg` by the holder of `pk`
+    pub fn verify_redjubjub(
+        &self,
+        pk: &EdwardsAffine,
+        msg: &[u8],
+        sig: &RedJubjubSignature,
+    ) -> bool {
+        RedJubjub::verify(&self.redjubjub_params(), pk, msg, sig).expect("verify doesn't fail")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn system() -> SchnorrSystem<EdwardsProjective> {
+        SchnorrSystem::from_domain(b"schnorr tests")
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let (user, other) = (
+            system.new_redjubjub_user(&mut rng),
+            system.new_redjubjub_user(&mut rng),
+        );
+        let sig = user.sign(b"message").unwrap();
+
+        assert!(system.verify_redjubjub(&user.pk, b"message", &sig));
+        assert!(!system.verify_redjubjub(&user.pk, b"massage", &sig));
+        assert!(!system.verify_redjubjub(&other.pk, b"message", &sig));
+    }
+
+    #[test]
+    /// Check that a re-randomized user's signatures verify under the re-randomized key, and only
+    /// under it
+    fn rerandomized_signatures() {
+        let system = system();
+        let mut rng = rand::thread_rng();
+        let user = system.new_redjubjub_user(&mut rng);
+        let alpha = JubjubFr::rand(&mut rng);
+        let spender = user.rerandomize(&alpha);
+
+        let mut alpha_bytes = Vec::new();
+        alpha.serialize_compressed(&mut alpha_bytes).unwrap();
+        assert_eq!(
+            RedJubjub::randomize_public_key(&user.params, &user.pk, &alpha_bytes).unwrap(),
+            spender.pk
+        );
+
+        let sig = spender.sign(b"message").unwrap();
+        assert!(system.verify_redjubjub(&spender.pk, b"message", &sig));
+        assert!(!system.verify_redjubjub(&user.pk, b"message", &sig));
+
+        let sig = user.sign(b"message").unwrap();
+        assert!(!system.verify_redjubjub(&spender.pk, b"message", &sig));
+    }
+
+    #[test]
+    /// Check that re-randomized keys only link back to the original with the right `alpha`. The
+    /// difference between the keys is `α·G`, which looks like any other public key.
+    fn unlinkable_without_alpha() {
+        let system = system();
+        let params = system.redjubjub_params();
+        let mut rng = rand::thread_rng();
+        let user = system.new_redjubjub_user(&mut rng);
+        let (alpha, beta) = (JubjubFr::rand(&mut rng), JubjubFr::rand(&mut rng));
+        let (first, second) = (user.rerandomize(&alpha), user.rerandomize(&beta));
+
+        assert_ne!(first.pk, user.pk);
+        assert_ne!(first.pk, second.pk);
+
+        assert_eq!(randomize_public_key(&params, &user.pk, &alpha), first.pk);
+        assert_ne!(randomize_public_key(&params, &user.pk, &beta), first.pk);
+        assert_ne!(
+            randomize_public_key(&params, &user.pk, &JubjubFr::rand(&mut rng)),
+            first.pk
+        );
+
+        // A signature doesn't give away the original key either
+        let sig = first.sign(b"message").unwrap();
+        assert!(!system.verify_redjubjub(&user.pk, b"message", &sig));
+        assert!(!system.verify_redjubjub(&second.pk, b"message", &sig));
+    }
+
+    #[test]
+    fn signature_serialization() {
+        let system = system();
+        let user = system.new_redjubjub_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        let mut bytes = Vec::new();
+        sig.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 64);
+
+        let decoded = RedJubjubSignature::deserialize_compressed(&bytes[..]).unwrap();
+        assert_eq!(decoded, sig);
+        assert!(system.verify_redjubjub(&user.pk, b"message", &decoded));
+    }
+
+    #[test]
+    /// Check that signatures don't verify under another system's generator
+    fn other_system() {
+        let system = system();
+        let other = SchnorrSystem::<EdwardsProjective>::from_domain(b"other deployment");
+        let user = system.new_redjubjub_user(&mut rand::thread_rng());
+        let sig = user.sign(b"message").unwrap();
+
+        assert!(!other.verify_redjubjub(&user.pk, b"message", &sig));
+    }
+}
@@ -0,0 +1,155 @@
+//! Runs the `schnorr` binary end to end
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+// A fresh directory for one test's files, and the paths of `files` in it
+fn scratch_dir<const N: usize>(name: &str, files: [&str; N]) -> (PathBuf, [String; N]) {
+    let dir = std::env::temp_dir().join(format!("schnorr-cli-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let paths = files.map(|f| dir.join(f).to_str().unwrap().to_string());
+    (dir, paths)
+}
+
+fn schnorr(args: &[&str], passphrase: Option<&str>, stdin: Option<&[u8]>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_schnorr"));
+    command
+        .args(args)
+        .env_remove("SCHNORR_PASSPHRASE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(passphrase) = passphrase {
+        command.env("SCHNORR_PASSPHRASE", passphrase);
+    }
+
+    let mut child = command.spawn().unwrap();
+    let mut child_stdin = child.stdin.take().unwrap();
+    child_stdin.write_all(stdin.unwrap_or_default()).unwrap();
+    drop(child_stdin);
+
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+/// Check keygen, sign, verify and pubkey on Jubjub keys, reading a message from stdin
+fn keygen_sign_verify_pubkey() {
+    let (dir, [sk, pk, msg, other_msg, sig, pk_again]) =
+        scratch_dir("jubjub", ["sk", "pk", "msg", "other", "sig", "pk2"]);
+    fs::write(&msg, b"hello").unwrap();
+    fs::write(&other_msg, b"goodbye").unwrap();
+
+    let keygen = schnorr(&["keygen", &sk, &pk], None, None);
+    assert!(keygen.status.success(), "{}", stderr(&keygen));
+    assert!(stdout(&keygen).contains("jubjub"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&sk).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let sign = schnorr(&["sign", &sk, "-", &sig], None, Some(b"hello"));
+    assert!(sign.status.success(), "{}", stderr(&sign));
+
+    let verify = schnorr(&["verify", &pk, &msg, &sig], None, None);
+    assert!(verify.status.success());
+    assert_eq!(stdout(&verify).trim(), "signature valid");
+
+    let verify = schnorr(&["verify", &pk, &other_msg, &sig], None, None);
+    assert!(!verify.status.success());
+    assert_eq!(stdout(&verify).trim(), "signature invalid");
+
+    let pubkey = schnorr(&["pubkey", &sk, &pk_again], None, None);
+    assert!(pubkey.status.success(), "{}", stderr(&pubkey));
+    assert_eq!(fs::read(&pk).unwrap(), fs::read(&pk_again).unwrap());
+
+    // A secret key isn't a public key
+    let verify = schnorr(&["verify", &sk, &msg, &sig], None, None);
+    assert!(!verify.status.success());
+    assert!(stderr(&verify).contains("expected a Public key"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+/// Check that no command overwrites an existing file
+fn no_overwriting() {
+    let (dir, [sk, sk2, pk, other_pk, msg, sig]) =
+        scratch_dir("overwrite", ["sk", "sk2", "pk", "pk2", "msg", "sig"]);
+    fs::write(&msg, b"hello").unwrap();
+
+    assert!(schnorr(&["keygen", &sk, &pk], None, None).status.success());
+    let sk_bytes = fs::read(&sk).unwrap();
+
+    let keygen = schnorr(&["keygen", &sk, &other_pk], None, None);
+    assert!(!keygen.status.success());
+    assert!(stderr(&keygen).contains("already exists"));
+    assert_eq!(fs::read(&sk).unwrap(), sk_bytes);
+
+    // The public key is checked before the secret key is written
+    let keygen = schnorr(&["keygen", &sk2, &pk], None, None);
+    assert!(!keygen.status.success());
+    assert!(!Path::new(&sk2).exists());
+
+    fs::write(&sig, b"not a signature").unwrap();
+    assert!(!schnorr(&["sign", &sk, &msg, &sig], None, None)
+        .status
+        .success());
+    assert_eq!(fs::read(&sig).unwrap(), b"not a signature");
+
+    assert!(!schnorr(&["pubkey", &sk, &pk], None, None).status.success());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+/// Check an encrypted BLS12-381 key, which is only usable with its passphrase
+fn encrypted_key() {
+    let (dir, [sk, pk, msg, sig, pk_again]) =
+        scratch_dir("encrypted", ["sk", "pk", "msg", "sig", "pk2"]);
+    fs::write(&msg, b"hello").unwrap();
+
+    let keygen = ["keygen", "--curve", "bls12-381", "--encrypt", &sk, &pk];
+    let without_passphrase = schnorr(&keygen, None, None);
+    assert!(!without_passphrase.status.success());
+    assert!(!Path::new(&sk).exists());
+
+    assert!(schnorr(&keygen, Some("correct horse"), None)
+        .status
+        .success());
+
+    let sign = ["sign", &sk, &msg, &sig];
+    assert!(stderr(&schnorr(&sign, None, None)).contains("passphrase is required"));
+    assert!(stderr(&schnorr(&sign, Some("battery staple"), None)).contains("wrong passphrase"));
+    assert!(!Path::new(&sig).exists());
+
+    assert!(schnorr(&sign, Some("correct horse"), None).status.success());
+    assert!(schnorr(&["verify", &pk, &msg, &sig], None, None)
+        .status
+        .success());
+
+    assert!(
+        schnorr(&["pubkey", &sk, &pk_again], Some("correct horse"), None)
+            .status
+            .success()
+    );
+    assert_eq!(fs::read(&pk).unwrap(), fs::read(&pk_again).unwrap());
+
+    fs::remove_dir_all(dir).unwrap();
+}